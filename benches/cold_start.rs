@@ -0,0 +1,49 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use criterion::{criterion_group, criterion_main};
+use criterion::{black_box, Criterion};
+
+use uap_rust::RegexDatabase;
+
+static RULES: &[u8] = include_bytes!("../resources/regexes.msgpack");
+
+static AGENT: &str = "Mozilla/5.0 (Linux; Android 4.4.2; C6833 Build/14.3.A.0.681) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/30.0.0.0 Mobile Safari/537.36\n";
+
+/// Starts a new process that loads the built-in database and parses one
+/// user agent. Build with `--features prebuilt` to compare.
+fn bench_process(c: &mut Criterion) {
+    c.bench_function("cold start process", |b| {
+    	b.iter(|| {
+    		let mut child = Command::new(env!("CARGO_BIN_EXE_uap"))
+    			.stdin(Stdio::piped())
+    			.stdout(Stdio::piped())
+    			.spawn()
+    			.unwrap();
+    		child.stdin.take().unwrap().write_all(AGENT.as_bytes()).unwrap();
+    		black_box(child.wait_with_output().unwrap());
+    	});
+    });
+}
+
+/// Compiles all rules at runtime, what the built-in database does without
+/// the `prebuilt` feature.
+fn bench_compile(c: &mut Criterion) {
+    c.bench_function("cold start compile and parse", |b| {
+    	b.iter(|| {
+    		let db = RegexDatabase::from_slice(RULES).unwrap();
+    		black_box(db.device(AGENT));
+    	});
+    });
+}
+
+fn config() -> Criterion {
+    Criterion::default().sample_size(10)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = bench_process, bench_compile
+}
+criterion_main!(benches);
@@ -146,5 +146,34 @@ fn bench_os(c: &mut Criterion) {
 	});
 }
 
-criterion_group!(benches, bench_device, bench_browser, bench_os);
+static BOT_AGENTS: [&str; 8] = [
+    "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+    "Mozilla/5.0 (Android 3.0; YRSpider; +http://www.yunrang.com/yrspider.html)",
+    "Mozilla/5.0 (compatible; Daum/4.1; +http://cs.daum.net/faq/15/4118.html?faqId=28966)",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0",
+    "Mozilla/5.0 (Linux; Android 4.1.1; V360 Build/JRO03C) AppleWebKit/535.19 (KHTML, like Gecko) Chrome/18.0.1025.166 Mobile Safari/535.19",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 5_1_1 like Mac OS X) AppleWebKit/534.46 (KHTML, like Gecko) Version/5.1 Mobile/9B206 Safari/7534.48.3",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/70.0.3538.102 Safari/537.36",
+    "curl/7.64.1",
+];
+
+fn bench_bot(c: &mut Criterion) {
+    c.bench_function("bot check", |b| {
+    	b.iter(|| {
+    		for agent in BOT_AGENTS.iter() {
+    			black_box(Parser::new(agent).is_bot());
+    		}
+    	});
+    });
+
+    c.bench_function("bot check via device", |b| {
+    	b.iter(|| {
+    		for agent in BOT_AGENTS.iter() {
+    			black_box(Parser::new(agent).device().family == "Spider");
+    		}
+    	});
+    });
+}
+
+criterion_group!(benches, bench_device, bench_bot, bench_browser, bench_os);
 criterion_main!(benches);
@@ -0,0 +1,71 @@
+//! With the `prebuilt` feature, compiles the rules of
+//! `resources/regexes.msgpack` into DFAs, which are embedded into the crate.
+//! The built-in database then needs no regex compilation on startup.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "prebuilt")]
+    prebuilt::build();
+}
+
+#[cfg(feature = "prebuilt")]
+mod prebuilt {
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use regex_automata::dfa::dense;
+
+    const RULES: &str = "resources/regexes.msgpack";
+
+    /// Keys of the categories in `regexes.msgpack`, in the order of the
+    /// output. Must match `src/matcher.rs`.
+    const CATEGORIES: [&str; 3] = ["b", "o", "d"];
+
+    /// Rules whose DFA would exceed these limits use the `regex` crate at
+    /// runtime. They are few, but take long to determinize.
+    const DFA_SIZE_LIMIT: usize = 1 << 19;
+    const DETERMINIZE_SIZE_LIMIT: usize = 1 << 20;
+
+    type RawRules = BTreeMap<String, Vec<BTreeMap<String, Option<String>>>>;
+
+    pub fn build() {
+        println!("cargo:rerun-if-changed={}", RULES);
+        let data = fs::read(RULES).expect("could not read the rules");
+        let rules: RawRules = rmp_serde::from_slice(&data).expect("could not decode the rules");
+        let big_endian = env::var("CARGO_CFG_TARGET_ENDIAN").is_ok_and(|e| e == "big");
+
+        let mut out = b"UAPD".to_vec();
+        for category in CATEGORIES.iter() {
+            let rules = &rules[*category];
+            push_u32(&mut out, rules.len());
+            for rule in rules {
+                let pattern = rule["r"].as_deref().expect("rule without a pattern");
+                let bytes = match compile(pattern) {
+                    Some(dfa) if big_endian => dfa.to_bytes_big_endian(),
+                    Some(dfa) => dfa.to_bytes_little_endian(),
+                    None => Vec::new(),
+                };
+                push_u32(&mut out, bytes.len());
+                out.extend_from_slice(&bytes);
+            }
+        }
+        let path = Path::new(&env::var("OUT_DIR").unwrap()).join("automata.bin");
+        fs::write(path, out).expect("could not write the automata");
+    }
+
+    /// Compiles `pattern` with the defaults of the `regex` crate.
+    fn compile(pattern: &str) -> Option<regex_automata::dfa::sparse::DFA<Vec<u8>>> {
+        let config = dense::Config::new()
+            .minimize(false)
+            .dfa_size_limit(Some(DFA_SIZE_LIMIT))
+            .determinize_size_limit(Some(DETERMINIZE_SIZE_LIMIT));
+        let dfa = dense::Builder::new().configure(config).build(pattern).ok()?;
+        dfa.to_sparse().ok()
+    }
+
+    fn push_u32(out: &mut Vec<u8>, n: usize) {
+        out.extend_from_slice(&(n as u32).to_le_bytes());
+    }
+}
@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_derive::Serialize;
+
+use crate::client_hints::ClientHints;
+use crate::UserAgentInformation;
+
+/// A headless browser or browser automation framework.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum AutomationTool {
+    /// Chrome in headless mode, also the default of Puppeteer and of
+    /// Playwright's Chromium.
+    HeadlessChrome,
+    PhantomJS,
+    SlimerJS,
+    /// Selenium or another WebDriver client.
+    Selenium,
+    /// The HTTP client of Playwright.
+    Playwright,
+    Cypress,
+    /// Electron apps, e.g. Nightmare.
+    Electron,
+    HtmlUnit,
+    Jsdom,
+}
+
+impl AutomationTool {
+    /// Returns the name of the tool, e.g. `"HeadlessChrome"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutomationTool::HeadlessChrome => "HeadlessChrome",
+            AutomationTool::PhantomJS => "PhantomJS",
+            AutomationTool::SlimerJS => "SlimerJS",
+            AutomationTool::Selenium => "Selenium",
+            AutomationTool::Playwright => "Playwright",
+            AutomationTool::Cypress => "Cypress",
+            AutomationTool::Electron => "Electron",
+            AutomationTool::HtmlUnit => "HtmlUnit",
+            AutomationTool::Jsdom => "jsdom",
+        }
+    }
+}
+
+impl fmt::Display for AutomationTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An automation tool detected in a user agent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Automation<'a> {
+    pub tool: AutomationTool,
+    pub version: Option<Cow<'a, str>>,
+}
+
+/// Tokens identifying the tools, matched ignoring ASCII case. Tools running
+/// in Electron or headless Chrome come first, they add their own token.
+static TOKENS: &[(&str, AutomationTool)] = &[
+    ("Cypress", AutomationTool::Cypress),
+    ("Playwright", AutomationTool::Playwright),
+    ("selenium", AutomationTool::Selenium),
+    ("webdriver", AutomationTool::Selenium),
+    ("PhantomJS", AutomationTool::PhantomJS),
+    ("SlimerJS", AutomationTool::SlimerJS),
+    ("HtmlUnit", AutomationTool::HtmlUnit),
+    ("jsdom", AutomationTool::Jsdom),
+    ("Electron", AutomationTool::Electron),
+    ("HeadlessChrome", AutomationTool::HeadlessChrome),
+];
+
+/// Returns the positions of `needle` in `haystack`, ignoring ASCII case.
+fn find_all_ci<'a>(haystack: &'a str, needle: &'a str) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .enumerate()
+        .filter(move |(_, w)| w.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|(i, _)| i)
+}
+
+pub(crate) fn detect(agent: &str) -> Option<Automation<'_>> {
+    TOKENS.iter().find_map(|&(token, tool)| {
+        find_all_ci(agent, token).find_map(|start| {
+            let rest = &agent[start + token.len()..];
+            // Only whole words, e.g. not `Electronics` or `NoSelenium`
+            if agent[..start].ends_with(|c: char| c.is_ascii_alphabetic())
+                || rest.starts_with(|c: char| c.is_ascii_alphabetic())
+            {
+                return None;
+            }
+            let version = rest.strip_prefix('/').and_then(|rest| {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-')
+                    .unwrap_or(rest.len());
+                Some(&rest[..end]).filter(|v| !v.is_empty())
+            });
+            Some(Automation {
+                tool,
+                version: version.map(Cow::Borrowed),
+            })
+        })
+    })
+}
+
+impl ClientHints {
+    /// Detects automation tools like `UserAgentInformation::automation`,
+    /// but also in the brands of the client hints. Headless Chrome reports
+    /// itself there even if the user agent was changed.
+    pub fn automation<'a, P>(&'a self, parser: &'a P) -> Option<Automation<'a>>
+    where
+        P: UserAgentInformation + ?Sized,
+    {
+        let headless = self
+            .full_version_list
+            .iter()
+            .chain(&self.brands)
+            .find(|brand| brand.brand == "HeadlessChrome");
+        match headless {
+            Some(brand) => Some(Automation {
+                tool: AutomationTool::HeadlessChrome,
+                version: Some(Cow::Borrowed(&brand.version)),
+            }),
+            None => parser.automation(),
+        }
+    }
+}
+
+#[test]
+fn test_detect() {
+    let automation = detect("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/120.0.6099.28 Safari/537.36").unwrap();
+    assert_eq!(automation.tool, AutomationTool::HeadlessChrome);
+    assert_eq!(automation.version.unwrap(), "120.0.6099.28");
+    let cypress = detect("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Cypress/12.17.4 Chrome/106.0.5249.51 Electron/21.0.0 Safari/537.36").unwrap();
+    assert_eq!(cypress.tool, AutomationTool::Cypress);
+    assert_eq!(
+        detect("Mozilla/5.0 (Linux; Android 9; Electronics Store)"),
+        None
+    );
+    // The first occurrence is part of a longer word
+    let electron = detect("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) ElectronicsStore/2.1.0 Chrome/66.0.3359.181 Electron/3.1.13 Safari/537.36").unwrap();
+    assert_eq!(electron.tool, AutomationTool::Electron);
+    assert_eq!(electron.version.unwrap(), "3.1.13");
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::slice;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::sync::BorrowingParser;
+use crate::ua_core::UA_PARSER_REGEXES;
+use crate::RegexDatabase;
+
+/// Parses a batch of user agents with the built-in database.
+///
+/// Identical user agents within the batch are parsed only once. The browser,
+/// operating system and device of each distinct user agent are parsed
+/// eagerly, with the `rayon` feature in parallel. `agents` can be a slice of
+/// `&str` or `String`, or any iterator over string references.
+///
+/// ```rust
+/// let agents = ["Firefox/60.0", "curl/7.64.1", "Firefox/60.0"];
+/// let batch = uap_rust::parse_all(&agents);
+/// assert_eq!(batch.len(), 3);
+/// assert_eq!(batch.unique().len(), 2);
+/// assert_eq!(batch[2].browser().family, "Firefox");
+/// ```
+pub fn parse_all<'a, I, S>(agents: I) -> Batch<'a>
+where
+    I: IntoIterator<Item = &'a S>,
+    S: AsRef<str> + ?Sized + 'a,
+{
+    Batch::parse(agents, &UA_PARSER_REGEXES)
+}
+
+/// The parsers of a batch of user agents, in the order of the input.
+///
+/// Parsers of identical user agents are shared, `unique` returns each of
+/// them once.
+pub struct Batch<'a> {
+    parsers: Vec<BorrowingParser<'a>>,
+    indices: Vec<usize>,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn parse<I, S>(agents: I, db: &'a RegexDatabase) -> Batch<'a>
+    where
+        I: IntoIterator<Item = &'a S>,
+        S: AsRef<str> + ?Sized + 'a,
+    {
+        let agents = agents.into_iter();
+        let mut seen: HashMap<&'a str, usize> = HashMap::new();
+        let mut parsers = Vec::new();
+        let mut indices = Vec::with_capacity(agents.size_hint().0);
+        for agent in agents {
+            let agent = agent.as_ref();
+            let index = *seen.entry(agent).or_insert_with(|| {
+                parsers.push(BorrowingParser::with_database(agent, db));
+                parsers.len() - 1
+            });
+            indices.push(index);
+        }
+        let batch = Batch { parsers, indices };
+        batch.parse_unique();
+        batch
+    }
+
+    #[cfg(feature = "rayon")]
+    fn parse_unique(&self) {
+        self.parsers.par_iter().for_each(parse_eagerly);
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn parse_unique(&self) {
+        self.parsers.iter().for_each(parse_eagerly);
+    }
+
+    /// Returns the number of user agents in the batch.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the parser of the user agent at `index` of the input.
+    pub fn get(&self, index: usize) -> Option<&BorrowingParser<'a>> {
+        self.indices.get(index).map(|&i| &self.parsers[i])
+    }
+
+    /// Iterates over the parsers in the order of the input.
+    pub fn iter(&self) -> Iter<'_, 'a> {
+        Iter {
+            parsers: &self.parsers,
+            indices: self.indices.iter(),
+        }
+    }
+
+    /// Returns the parsers of the distinct user agents in the order of their
+    /// first occurrence.
+    pub fn unique(&self) -> &[BorrowingParser<'a>] {
+        &self.parsers
+    }
+}
+
+fn parse_eagerly(parser: &BorrowingParser<'_>) {
+    parser.browser();
+    parser.os();
+    parser.device();
+}
+
+impl<'a> Index<usize> for Batch<'a> {
+    type Output = BorrowingParser<'a>;
+
+    fn index(&self, index: usize) -> &BorrowingParser<'a> {
+        &self.parsers[self.indices[index]]
+    }
+}
+
+impl<'b, 'a> IntoIterator for &'b Batch<'a> {
+    type Item = &'b BorrowingParser<'a>;
+    type IntoIter = Iter<'b, 'a>;
+
+    fn into_iter(self) -> Iter<'b, 'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the parsers of a `Batch` in the order of the input.
+#[derive(Clone)]
+pub struct Iter<'b, 'a> {
+    parsers: &'b [BorrowingParser<'a>],
+    indices: slice::Iter<'b, usize>,
+}
+
+impl<'b, 'a> Iterator for Iter<'b, 'a> {
+    type Item = &'b BorrowingParser<'a>;
+
+    fn next(&mut self) -> Option<&'b BorrowingParser<'a>> {
+        self.indices.next().map(|&i| &self.parsers[i])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'b, 'a> ExactSizeIterator for Iter<'b, 'a> {}
+
+#[test]
+fn test_parse_all() {
+    let agents = vec![
+        "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0".to_string(),
+        "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)".to_string(),
+        "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0".to_string(),
+        "curl/7.64.1".to_string(),
+    ];
+    let batch = parse_all(&agents);
+    assert_eq!(batch.len(), 4);
+    assert_eq!(batch.unique().len(), 3);
+    assert!(std::ptr::eq(&batch[0], &batch[2]));
+    let agents_out: Vec<_> = batch.iter().map(|p| p.user_agent()).collect();
+    assert_eq!(agents_out, agents);
+    let families: Vec<_> = batch.iter().map(|p| &p.browser().family[..]).collect();
+    assert_eq!(families, vec!["Firefox", "Googlebot", "Firefox", "curl"]);
+    assert!(batch.get(1).unwrap().is_bot());
+    assert!(batch.get(4).is_none());
+    assert!(parse_all::<_, str>(Vec::new()).is_empty());
+}
+
+#[test]
+fn test_parse_all_with_database() {
+    let db = RegexDatabase::from_yaml(
+        "user_agent_parsers:\n  - regex: '(Foo)/(\\d+)'\nos_parsers: []\ndevice_parsers: []",
+    )
+    .unwrap();
+    let lines = "Foo/1\nBar/2\nFoo/1";
+    let batch = db.parse_all(lines.lines());
+    let families: Vec<_> = batch.iter().map(|p| &p.browser().family[..]).collect();
+    assert_eq!(families, vec!["Foo", "Other", "Foo"]);
+}
@@ -0,0 +1,345 @@
+//! Parses user agents, one per line, from stdin or files.
+//!
+//! Run `uap --help` for the available options.
+use std::borrow::Cow;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+use std::sync::Arc;
+
+use uap_rust::unsync::BorrowingParser as Parser;
+use uap_rust::RegexDatabase;
+
+static USAGE: &str = "\
+Usage: uap [OPTIONS] [FILE]...
+
+Parses the user agents in FILE, one per line, and prints the browser,
+operating system and device of each. Reads stdin if no FILE is given
+or FILE is -.
+
+Options:
+  -f, --format FORMAT   output format: jsonl (default), csv or tsv
+      --fields FIELDS   comma separated list of fields to print
+      --only-unmatched  only print user agents for which the family of a
+                        category in FIELDS is \"Other\"
+      --no-header       do not print a header line for csv and tsv
+      --regexes FILE    use the rules of a regexes.yaml or msgpack file
+  -h, --help            print this help
+
+Fields:
+  user_agent, browser_family, browser_major, browser_minor, browser_patch,
+  os_family, os_major, os_minor, os_patch, os_patch_minor, device_family,
+  device_brand, device_model";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Browser,
+    OS,
+    Device,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    UserAgent,
+    BrowserFamily,
+    BrowserMajor,
+    BrowserMinor,
+    BrowserPatch,
+    OSFamily,
+    OSMajor,
+    OSMinor,
+    OSPatch,
+    OSPatchMinor,
+    DeviceFamily,
+    DeviceBrand,
+    DeviceModel,
+}
+
+static FIELDS: &[(&str, Field)] = &[
+    ("user_agent", Field::UserAgent),
+    ("browser_family", Field::BrowserFamily),
+    ("browser_major", Field::BrowserMajor),
+    ("browser_minor", Field::BrowserMinor),
+    ("browser_patch", Field::BrowserPatch),
+    ("os_family", Field::OSFamily),
+    ("os_major", Field::OSMajor),
+    ("os_minor", Field::OSMinor),
+    ("os_patch", Field::OSPatch),
+    ("os_patch_minor", Field::OSPatchMinor),
+    ("device_family", Field::DeviceFamily),
+    ("device_brand", Field::DeviceBrand),
+    ("device_model", Field::DeviceModel),
+];
+
+impl Field {
+    fn name(self) -> &'static str {
+        FIELDS.iter().find(|(_, f)| *f == self).unwrap().0
+    }
+
+    fn category(self) -> Option<Category> {
+        match self {
+            Field::UserAgent => None,
+            Field::BrowserFamily
+            | Field::BrowserMajor
+            | Field::BrowserMinor
+            | Field::BrowserPatch => Some(Category::Browser),
+            Field::OSFamily
+            | Field::OSMajor
+            | Field::OSMinor
+            | Field::OSPatch
+            | Field::OSPatchMinor => Some(Category::OS),
+            Field::DeviceFamily | Field::DeviceBrand | Field::DeviceModel => Some(Category::Device),
+        }
+    }
+
+    fn value<'a>(self, parser: &'a Parser<'_>) -> Option<&'a str> {
+        match self {
+            Field::UserAgent => Some(parser.user_agent()),
+            Field::BrowserFamily => Some(&parser.browser().family),
+            Field::BrowserMajor => parser.browser().major.as_deref(),
+            Field::BrowserMinor => parser.browser().minor.as_deref(),
+            Field::BrowserPatch => parser.browser().patch.as_deref(),
+            Field::OSFamily => Some(&parser.os().family),
+            Field::OSMajor => parser.os().major.as_deref(),
+            Field::OSMinor => parser.os().minor.as_deref(),
+            Field::OSPatch => parser.os().patch.as_deref(),
+            Field::OSPatchMinor => parser.os().patch_minor.as_deref(),
+            Field::DeviceFamily => Some(&parser.device().family),
+            Field::DeviceBrand => parser.device().brand.as_deref(),
+            Field::DeviceModel => parser.device().model.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    JsonLines,
+    Csv,
+    Tsv,
+}
+
+struct Options {
+    format: Format,
+    fields: Vec<Field>,
+    only_unmatched: bool,
+    header: bool,
+    regexes: Option<String>,
+    files: Vec<String>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        format: Format::JsonLines,
+        fields: FIELDS.iter().map(|(_, f)| *f).collect(),
+        only_unmatched: false,
+        header: true,
+        regexes: None,
+        files: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_string())),
+            _ => (&arg[..], None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+        match name {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "-f" | "--format" => {
+                options.format = match &value()?[..] {
+                    "jsonl" | "json" => Format::JsonLines,
+                    "csv" => Format::Csv,
+                    "tsv" => Format::Tsv,
+                    other => return Err(format!("unknown format: {}", other)),
+                }
+            }
+            "--fields" => {
+                options.fields = value()?
+                    .split(',')
+                    .map(|name| {
+                        FIELDS
+                            .iter()
+                            .find(|(n, _)| *n == name.trim())
+                            .map(|(_, f)| *f)
+                            .ok_or_else(|| format!("unknown field: {}", name))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--only-unmatched" => options.only_unmatched = true,
+            "--no-header" => options.header = false,
+            "--regexes" => options.regexes = Some(value()?),
+            "-" => options.files.push(arg.clone()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => options.files.push(arg.clone()),
+        }
+    }
+    if options.files.is_empty() {
+        options.files.push("-".into());
+    }
+    Ok(options)
+}
+
+fn load_database(path: &str) -> Result<Arc<RegexDatabase>, uap_rust::Error> {
+    let db = if path.ends_with(".yaml") || path.ends_with(".yml") {
+        RegexDatabase::from_yaml_file(path)?
+    } else {
+        RegexDatabase::from_file(path)?
+    };
+    Ok(Arc::new(db))
+}
+
+/// Returns `true` if the family of a category used by `fields` is "Other".
+fn is_unmatched(parser: &Parser<'_>, fields: &[Field]) -> bool {
+    let used = |category| fields.iter().any(|f| f.category() == Some(category));
+    let all = !fields.iter().any(|f| f.category().is_some());
+    ((all || used(Category::Browser)) && parser.browser().family == "Other")
+        || ((all || used(Category::OS)) && parser.os().family == "Other")
+        || ((all || used(Category::Device)) && parser.device().family == "Other")
+}
+
+fn quote_json(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn quote_csv(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+fn quote_tsv(value: &str) -> Cow<'_, str> {
+    if value.contains(['\t', '\n', '\r', '\\']) {
+        value
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .into()
+    } else {
+        value.into()
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, options: &Options) -> io::Result<()> {
+    let separator = match options.format {
+        Format::JsonLines => return Ok(()),
+        Format::Csv => ",",
+        Format::Tsv => "\t",
+    };
+    let names: Vec<_> = options.fields.iter().map(|f| f.name()).collect();
+    writeln!(out, "{}", names.join(separator))
+}
+
+fn write_record<W: Write>(out: &mut W, parser: &Parser<'_>, options: &Options) -> io::Result<()> {
+    let values = options.fields.iter().map(|f| (f.name(), f.value(parser)));
+    match options.format {
+        Format::JsonLines => {
+            let members: Vec<_> = values
+                .map(|(name, value)| {
+                    let value = value.map_or_else(|| "null".into(), quote_json);
+                    format!("{}:{}", quote_json(name), value)
+                })
+                .collect();
+            writeln!(out, "{{{}}}", members.join(","))
+        }
+        Format::Csv => {
+            let values: Vec<_> = values.map(|(_, v)| quote_csv(v.unwrap_or(""))).collect();
+            writeln!(out, "{}", values.join(","))
+        }
+        Format::Tsv => {
+            let values: Vec<_> = values.map(|(_, v)| quote_tsv(v.unwrap_or(""))).collect();
+            writeln!(out, "{}", values.join("\t"))
+        }
+    }
+}
+
+fn process<R: BufRead, W: Write>(
+    input: R,
+    out: &mut W,
+    db: &RegexDatabase,
+    options: &Options,
+) -> io::Result<()> {
+    for line in input.split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let agent = line.trim_end_matches('\r');
+        if agent.is_empty() {
+            continue;
+        }
+        let parser = Parser::with_database(agent, db);
+        if options.only_unmatched && !is_unmatched(&parser, &options.fields) {
+            continue;
+        }
+        write_record(out, &parser, options)?;
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let db = match &options.regexes {
+        Some(path) => {
+            load_database(path).map_err(|e| io::Error::other(format!("{}: {}", path, e)))?
+        }
+        None => RegexDatabase::builtin(),
+    };
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if options.header {
+        write_header(&mut out, options)?;
+    }
+    for file in &options.files {
+        let context = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", file, e));
+        if file == "-" {
+            let stdin = io::stdin();
+            let input = stdin.lock();
+            process(input, &mut out, &db, options).map_err(context)?;
+        } else {
+            let input = File::open(file).map_err(context)?;
+            process(BufReader::new(input), &mut out, &db, options).map_err(context)?;
+        }
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("uap: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(&options) {
+        // Output piped into e.g. `head` is not an error
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+        Err(e) => {
+            eprintln!("uap: {}", e);
+            process::exit(1);
+        }
+        Ok(()) => (),
+    }
+}
@@ -0,0 +1,384 @@
+//! Bot detection without parsing the device.
+//!
+//! A user agent is a bot if the first matching device rule produces the
+//! device "Spider". Only a handful of rules do, so instead of trying the
+//! whole device list these rules are combined into a single `RegexSet`.
+//! Most user agents match none of them and are decided in one pass.
+//!
+//! Rules taking the device from capture groups might produce "Spider" as
+//! well. Rules which take it from a single group only can do so for user
+//! agents containing "Spider", the few others are checked with a separate
+//! set. For user agents which might get "Spider" from a group, another set
+//! includes all of these rules, if one of them matches first its device is
+//! computed to decide.
+use once_cell::sync::OnceCell;
+use regex::{RegexSet, RegexSetBuilder};
+use regex_syntax::hir::literal::Extractor;
+use regex_syntax::hir::{Hir, HirKind};
+use regex_syntax::Parser;
+
+/// Whether a device rule produces the device "Spider".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpiderRule {
+    Always,
+    /// The device is a capture group, which might be "Spider".
+    Captured,
+    /// The device combines capture groups and text, which might result in
+    /// "Spider".
+    Maybe,
+    Never,
+}
+
+impl SpiderRule {
+    /// Classifies a device rule by its `pattern` and `device_replacement`.
+    /// Without a replacement the device is the first capture group.
+    pub(crate) fn of(pattern: &str, replacement: Option<&str>) -> SpiderRule {
+        let template = parse_template(replacement.unwrap_or("$1"));
+        let references = template.iter().filter(|part| part.is_err()).count();
+        if references == 0 {
+            let device: String = template.into_iter().flatten().collect();
+            return if device.trim_matches(' ') == "Spider" {
+                SpiderRule::Always
+            } else {
+                SpiderRule::Never
+            };
+        }
+        let hir = Parser::new().parse(pattern).ok();
+        let parts: Vec<_> = template
+            .iter()
+            .map(|part| match part {
+                Ok(text) => vec![Expansion::exact(text)],
+                Err(group) => expansions(hir.as_ref(), *group),
+            })
+            .collect();
+        if !may_expand_to(&parts, "Spider") {
+            return SpiderRule::Never;
+        }
+        let only_reference = template.iter().all(|part| {
+            part.as_ref()
+                .map_or(true, |text| text.trim_matches(' ').is_empty())
+        });
+        if references == 1 && only_reference {
+            SpiderRule::Captured
+        } else {
+            SpiderRule::Maybe
+        }
+    }
+}
+
+/// Splits a replacement template into text and references to capture
+/// groups like `$1` or `${1}`. Named groups are referenced as `None`.
+fn parse_template(template: &str) -> Vec<Result<String, Option<u32>>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            text.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        if chars.next_if_eq(&'{').is_some() {
+            name.extend(chars.by_ref().take_while(|&c| c != '}'));
+        } else {
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+        }
+        parts.push(Ok(std::mem::take(&mut text)));
+        parts.push(Err(name.parse().ok()));
+    }
+    parts.push(Ok(text));
+    parts
+}
+
+/// A possible expansion of a part of a template: `text`, followed by
+/// anything if `open`.
+struct Expansion {
+    text: String,
+    open: bool,
+}
+
+impl Expansion {
+    fn exact(text: &str) -> Expansion {
+        Expansion {
+            text: text.to_string(),
+            open: false,
+        }
+    }
+
+    fn anything() -> Expansion {
+        Expansion {
+            text: String::new(),
+            open: true,
+        }
+    }
+}
+
+/// Returns the possible expansions of a reference to capture `group`,
+/// from the literals the group can start with.
+fn expansions(hir: Option<&Hir>, group: Option<u32>) -> Vec<Expansion> {
+    let found = match (hir, group) {
+        (Some(hir), Some(group)) => find_group(hir, group, false),
+        _ => None,
+    };
+    let (sub, optional) = match found {
+        Some(found) => found,
+        // Missing groups expand to nothing
+        None if hir.is_some() && group.is_some() => return vec![Expansion::exact("")],
+        None => return vec![Expansion::anything()],
+    };
+    let literals = Extractor::new().extract(sub);
+    let mut expansions: Vec<_> = match literals.literals() {
+        Some(literals) => literals
+            .iter()
+            .map(|literal| Expansion {
+                text: String::from_utf8_lossy(literal.as_bytes()).into_owned(),
+                open: !literal.is_exact(),
+            })
+            .collect(),
+        None => vec![Expansion::anything()],
+    };
+    // A group which does not participate expands to nothing
+    if optional {
+        expansions.push(Expansion::exact(""));
+    }
+    expansions
+}
+
+/// Returns the capture group with the index `group` and whether it may not
+/// participate in a match.
+fn find_group(hir: &Hir, group: u32, optional: bool) -> Option<(&Hir, bool)> {
+    match hir.kind() {
+        HirKind::Capture(capture) if capture.index == group => Some((&capture.sub, optional)),
+        HirKind::Capture(capture) => find_group(&capture.sub, group, optional),
+        HirKind::Repetition(rep) => find_group(&rep.sub, group, optional || rep.min == 0),
+        HirKind::Concat(subs) => subs.iter().find_map(|sub| find_group(sub, group, optional)),
+        HirKind::Alternation(subs) => subs.iter().find_map(|sub| find_group(sub, group, true)),
+        _ => None,
+    }
+}
+
+/// Returns `true` if a concatenation of one expansion of each part may be
+/// `target` after trimming spaces.
+fn may_expand_to(parts: &[Vec<Expansion>], target: &str) -> bool {
+    let target = target.as_bytes();
+    // Positions in `target` reachable after the parts so far
+    let mut reachable = vec![false; target.len() + 1];
+    reachable[0] = true;
+    for part in parts {
+        let mut next = vec![false; target.len() + 1];
+        for expansion in part {
+            for start in (0..reachable.len()).filter(|&p| reachable[p]) {
+                let end = expansion.text.bytes().try_fold(start, |p, b| match b {
+                    b' ' if p == 0 || p == target.len() => Some(p),
+                    _ if p < target.len() && target[p] == b => Some(p + 1),
+                    _ => None,
+                });
+                match end {
+                    Some(end) if expansion.open => next[end..].iter_mut().for_each(|r| *r = true),
+                    Some(end) => next[end] = true,
+                    None => (),
+                }
+            }
+        }
+        reachable = next;
+    }
+    reachable[target.len()]
+}
+
+/// Result of `BotMatcher::check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict<T> {
+    /// No rule producing "Spider" matches, the user agent is not a bot.
+    Human,
+    /// A rule producing "Spider" matches and no other rule can match before
+    /// it.
+    Bot(T),
+    /// A rule producing "Spider" matches, but an earlier device rule may
+    /// match as well.
+    Unsure,
+}
+
+/// Device rules combined into a `RegexSet`.
+#[derive(Debug)]
+struct Combined {
+    /// `None` if there are no rules or they are too large to be combined.
+    set: Option<RegexSet>,
+    /// The position of each pattern of `set` in the device rules and its
+    /// classification.
+    rules: Vec<(usize, SpiderRule)>,
+    /// The number of rules of `set` at the start of the device rules.
+    leading: usize,
+}
+
+impl Combined {
+    fn new(rules: &[(String, SpiderRule)], include: impl Fn(SpiderRule) -> bool) -> Combined {
+        let mut patterns = Vec::new();
+        let mut positions = Vec::new();
+        let mut leading = 0;
+        for (position, (pattern, spider)) in rules.iter().enumerate() {
+            if !include(*spider) {
+                continue;
+            }
+            if position == leading {
+                leading += 1;
+            }
+            patterns.push(pattern);
+            positions.push((position, *spider));
+        }
+        let set = if patterns.is_empty() {
+            None
+        } else {
+            RegexSetBuilder::new(&patterns)
+                .size_limit(4 * 10_485_760)
+                .build()
+                .ok()
+        };
+        Combined {
+            set,
+            rules: positions,
+            leading,
+        }
+    }
+
+    /// Returns `true` if any of the rules may match `agent`.
+    fn is_match(&self, agent: &str) -> bool {
+        match &self.set {
+            Some(set) => set.is_match(agent),
+            None => !self.rules.is_empty(),
+        }
+    }
+
+    fn check<F>(&self, agent: &str, is_spider: F) -> Verdict<usize>
+    where
+        F: FnOnce(usize) -> bool,
+    {
+        let set = match &self.set {
+            Some(set) => set,
+            None if self.rules.is_empty() => return Verdict::Human,
+            None => return Verdict::Unsure,
+        };
+        let i = match set.matches(agent).iter().next() {
+            Some(i) => i,
+            None => return Verdict::Human,
+        };
+        // Earlier rules not in `set` produce other devices, if one of them
+        // matches instead the user agent is no bot either
+        let (position, spider) = self.rules[i];
+        if spider != SpiderRule::Always && !is_spider(position) {
+            return Verdict::Human;
+        }
+        if i < self.leading {
+            Verdict::Bot(position)
+        } else {
+            Verdict::Unsure
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct BotMatcher {
+    /// All device rules, to combine `all` on first use.
+    rules: Vec<(String, SpiderRule)>,
+    /// The rules which always produce "Spider".
+    spiders: Combined,
+    /// The `SpiderRule::Maybe` rules.
+    maybe: Combined,
+    /// The rules which may produce "Spider", needed for user agents
+    /// containing "Spider" or matching a `SpiderRule::Maybe` rule.
+    all: OnceCell<Combined>,
+}
+
+impl BotMatcher {
+    /// Combines the device rules which may produce "Spider". `rules` are
+    /// the patterns of all device rules in order.
+    pub(crate) fn new<'a, I>(rules: I) -> BotMatcher
+    where
+        I: IntoIterator<Item = (&'a str, SpiderRule)>,
+    {
+        let rules: Vec<_> = rules
+            .into_iter()
+            .map(|(pattern, spider)| (pattern.to_string(), spider))
+            .collect();
+        BotMatcher {
+            spiders: Combined::new(&rules, |spider| spider == SpiderRule::Always),
+            maybe: Combined::new(&rules, |spider| spider == SpiderRule::Maybe),
+            all: OnceCell::INIT,
+            rules,
+        }
+    }
+
+    /// Checks `agent`, on `Verdict::Bot` returns the position of the
+    /// device rule which matches first. `is_spider` tells whether the
+    /// rule at a position produces "Spider" for `agent`, it is only called
+    /// for rules which do not always.
+    pub(crate) fn check<F>(&self, agent: &str, is_spider: F) -> Verdict<usize>
+    where
+        F: FnOnce(usize) -> bool,
+    {
+        // A captured device can only be "Spider" if the user agent contains it
+        if !agent.contains("Spider") && !self.maybe.is_match(agent) {
+            return self.spiders.check(agent, is_spider);
+        }
+        self.all
+            .get_or_init(|| Combined::new(&self.rules, |spider| spider != SpiderRule::Never))
+            .check(agent, is_spider)
+    }
+}
+
+#[test]
+fn test_bot_matcher() {
+    let bots = BotMatcher::new(vec![
+        ("Googlebot", SpiderRule::Always),
+        ("(Foo)Phone", SpiderRule::Never),
+        ("(?i)bot", SpiderRule::Always),
+        ("(Sp\\w+)", SpiderRule::Maybe),
+    ]);
+    let check = |agent: &str| bots.check(agent, |_| agent.contains("Spider"));
+    assert_eq!(check("Googlebot/2.1"), Verdict::Bot(0));
+    assert_eq!(check("Mozilla/5.0 FooPhone"), Verdict::Human);
+    assert_eq!(check("FooPhone SomeBot"), Verdict::Unsure);
+    assert_eq!(check("Spider"), Verdict::Unsure);
+    assert_eq!(check("Spinner"), Verdict::Human);
+    let captured = BotMatcher::new(vec![
+        ("Googlebot", SpiderRule::Always),
+        ("(\\w+)Crawler", SpiderRule::Captured),
+    ]);
+    let check = |agent: &str| captured.check(agent, |_| agent.starts_with("Spider"));
+    assert_eq!(check("FooCrawler"), Verdict::Human);
+    assert!(captured.all.get().is_none());
+    assert_eq!(check("SpiderCrawler"), Verdict::Bot(1));
+    assert_eq!(check("Googlebot SpiderCrawler"), Verdict::Bot(0));
+    assert_eq!(check("FooCrawler Spider"), Verdict::Human);
+    assert_eq!(
+        BotMatcher::new(vec![]).check("Googlebot", |_| true),
+        Verdict::Human
+    );
+
+    let of = |replacement| SpiderRule::of("(\\w+)/(\\d+)", replacement);
+    assert_eq!(of(Some("Spider")), SpiderRule::Always);
+    assert_eq!(of(None), SpiderRule::Captured);
+    assert_eq!(of(Some("$1")), SpiderRule::Captured);
+    assert_eq!(of(Some("Sp${1}r")), SpiderRule::Maybe);
+    assert_eq!(of(Some("S$1d$2")), SpiderRule::Maybe);
+    assert_eq!(of(Some("Samsung $1")), SpiderRule::Never);
+    assert_eq!(of(Some("$1 Phone")), SpiderRule::Never);
+    assert_eq!(of(Some("Generic Smartphone")), SpiderRule::Never);
+    assert_eq!(SpiderRule::of("(iPhone|iPad);", None), SpiderRule::Never);
+    assert_eq!(
+        SpiderRule::of("(Bot|Spider)/", Some("${1}")),
+        SpiderRule::Captured
+    );
+    assert_eq!(SpiderRule::of("(?i)(spider)", None), SpiderRule::Captured);
+    // Groups starting with other text, unless they may not participate
+    let of = |pattern| SpiderRule::of(pattern, Some("$1$2"));
+    assert_eq!(of("(Acer )([^;]+)"), SpiderRule::Never);
+    assert_eq!(of("(Acer )?([UY][^;]+)"), SpiderRule::Never);
+    assert_eq!(of("(Acer )?([^;]+)"), SpiderRule::Maybe);
+    assert_eq!(of("(?:(Acer )|(Sp[^;]+))"), SpiderRule::Maybe);
+    assert_eq!(
+        SpiderRule::of("(\\w+);(\\w*)", Some("$1 $2")),
+        SpiderRule::Maybe
+    );
+}
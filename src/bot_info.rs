@@ -0,0 +1,338 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+use serde_yaml::Value;
+
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Error, UserAgentInformation};
+
+/// What a bot is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BotCategory {
+    /// Crawlers of search engines, e.g. Googlebot.
+    SearchEngine,
+    /// Crawlers of SEO and marketing tools, e.g. AhrefsBot.
+    Seo,
+    /// Uptime and performance monitoring, e.g. UptimeRobot.
+    Monitoring,
+    /// RSS and Atom readers, e.g. Feedly.
+    FeedFetcher,
+    /// Link previews of social networks and messengers, e.g. Slackbot.
+    SocialPreview,
+    /// Crawlers collecting training data or answering prompts for AI
+    /// models, e.g. GPTBot.
+    AiCrawler,
+    /// Command-line tools and HTTP client libraries, e.g. curl.
+    HttpLibrary,
+    /// Vulnerability and port scanners, e.g. Nmap.
+    SecurityScanner,
+    /// Bots of unknown purpose.
+    Other,
+}
+
+impl BotCategory {
+    const ALL: [BotCategory; 9] = [
+        BotCategory::SearchEngine,
+        BotCategory::Seo,
+        BotCategory::Monitoring,
+        BotCategory::FeedFetcher,
+        BotCategory::SocialPreview,
+        BotCategory::AiCrawler,
+        BotCategory::HttpLibrary,
+        BotCategory::SecurityScanner,
+        BotCategory::Other,
+    ];
+
+    /// Returns the name of the category in snake case, e.g.
+    /// `"search_engine"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BotCategory::SearchEngine => "search_engine",
+            BotCategory::Seo => "seo",
+            BotCategory::Monitoring => "monitoring",
+            BotCategory::FeedFetcher => "feed_fetcher",
+            BotCategory::SocialPreview => "social_preview",
+            BotCategory::AiCrawler => "ai_crawler",
+            BotCategory::HttpLibrary => "http_library",
+            BotCategory::SecurityScanner => "security_scanner",
+            BotCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for BotCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The bot a user agent belongs to, see `BotTable`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BotInfo<'a> {
+    pub name: Cow<'a, str>,
+    /// The company or project running the bot, if known.
+    pub operator: Option<Cow<'a, str>>,
+    pub category: BotCategory,
+}
+
+/// An entry of a `BotTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BotEntry {
+    /// Text identifying the bot in the user agent, matched ignoring ASCII
+    /// case.
+    pub token: String,
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub operator: Option<String>,
+    pub category: BotCategory,
+    /// Domains of the reverse DNS names of the bot's addresses in lower
+    /// case, used by `BotVerifier`. Empty if the operator does not publish
+    /// any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hostnames: Vec<String>,
+}
+
+impl BotEntry {
+    pub fn new(token: &str, name: &str, operator: Option<&str>, category: BotCategory) -> Self {
+        BotEntry {
+            token: token.into(),
+            name: name.into(),
+            operator: operator.map(Into::into),
+            category,
+            hostnames: Vec::new(),
+        }
+    }
+
+    /// Reads the entry at `index` of a YAML list, see `BotTable::extend_yaml`.
+    fn from_yaml(index: usize, mut fields: BTreeMap<String, Value>) -> Result<Self, Error> {
+        let invalid = |field: &str| Error::Invalid(format!("bot {} has no valid {}", index, field));
+        let mut string = |field: &str| match fields.remove(field) {
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(Value::Number(n)) => Ok(Some(n.to_string())),
+            None | Some(Value::Null) => Ok(None),
+            Some(_) => Err(invalid(field)),
+        };
+        let token = string("token")?.ok_or_else(|| invalid("token"))?;
+        let name = string("name")?.ok_or_else(|| invalid("name"))?;
+        let operator = string("operator")?;
+        let category = string("category")?
+            .and_then(|c| BotCategory::ALL.iter().find(|k| k.as_str() == c).copied())
+            .ok_or_else(|| invalid("category"))?;
+        let hostnames = match fields.remove("hostnames") {
+            Some(Value::Sequence(hostnames)) => hostnames
+                .into_iter()
+                .map(|h| match h {
+                    Value::String(h) => Ok(h),
+                    _ => Err(invalid("hostnames")),
+                })
+                .collect::<Result<_, _>>()?,
+            None | Some(Value::Null) => Vec::new(),
+            Some(_) => return Err(invalid("hostnames")),
+        };
+        Ok(BotEntry {
+            token,
+            name,
+            operator,
+            category,
+            hostnames,
+        })
+    }
+}
+
+static BUILTIN_BOTS: &str = include_str!("../resources/bots.yaml");
+
+lazy_static! {
+    static ref BUILTIN: BotTable = BotTable::builtin();
+}
+
+/// Identifies bots by tokens in the user agent.
+///
+/// The built-in table knows common search engines, SEO tools, monitoring
+/// services, feed readers, link previews, AI crawlers, HTTP libraries and
+/// security scanners. More bots can be added at runtime. If several tokens
+/// occur in a user agent, the longest one wins, of equally long ones the
+/// entry added last. Entries can thus be made more specific, e.g.
+/// `googlebot-image` wins over `googlebot`, and added entries override
+/// built-in ones with the same token.
+///
+/// ```rust
+/// use uap_rust::{BotCategory, BotEntry, BotTable};
+///
+/// let mut bots = BotTable::builtin();
+/// bots.push(BotEntry::new("examplebot", "ExampleBot", Some("Example Inc."), BotCategory::Monitoring));
+/// let bot = bots.lookup_user_agent("Mozilla/5.0 (compatible; ExampleBot/1.0)").unwrap();
+/// assert_eq!(bot.category, BotCategory::Monitoring);
+/// let bot = bots.lookup_user_agent("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)").unwrap();
+/// assert_eq!((&bot.name[..], bot.operator.as_deref()), ("Googlebot", Some("Google")));
+/// ```
+#[derive(Debug, Default)]
+pub struct BotTable {
+    entries: Vec<BotEntry>,
+    /// Searches for the tokens of `entries`, built on first use.
+    matcher: OnceCell<AhoCorasick>,
+}
+
+impl BotTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        BotTable::default()
+    }
+
+    /// Creates a table with the built-in entries of `resources/bots.yaml`.
+    pub fn builtin() -> Self {
+        let mut table = BotTable::new();
+        table
+            .extend_yaml(BUILTIN_BOTS)
+            .unwrap_or_else(|err| panic!("invalid built-in bot table: {}", err));
+        table
+    }
+
+    pub fn entries(&self) -> &[BotEntry] {
+        &self.entries
+    }
+
+    /// Adds `entry`, which takes precedence over entries with the same
+    /// token.
+    pub fn push(&mut self, entry: BotEntry) {
+        self.entries.push(entry);
+        self.matcher = OnceCell::INIT;
+    }
+
+    /// Adds the entries of a YAML list like
+    ///
+    /// ```yaml
+    /// - token: examplebot
+    ///   name: ExampleBot
+    ///   operator: Example Inc.
+    ///   category: monitoring
+    /// ```
+    ///
+    /// The categories are written in snake case, see `BotCategory::as_str`.
+    pub fn extend_yaml(&mut self, yaml: &str) -> Result<(), Error> {
+        let entries: Vec<BTreeMap<String, Value>> = serde_yaml::from_str(yaml)?;
+        let entries = entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, fields)| BotEntry::from_yaml(index, fields))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.extend(entries);
+        Ok(())
+    }
+
+    /// Returns the bot whose token occurs in `user_agent`.
+    pub fn lookup_user_agent(&self, user_agent: &str) -> Option<BotInfo<'_>> {
+        let entry = self.find(user_agent)?;
+        Some(BotInfo {
+            name: Cow::Borrowed(&entry.name),
+            operator: entry.operator.as_deref().map(Cow::Borrowed),
+            category: entry.category,
+        })
+    }
+
+    /// Returns the bot of a parsed user agent. Bots recognized by the
+    /// parser but missing from the table are reported with the browser
+    /// family as name and `BotCategory::Other`.
+    pub fn lookup<'a, P>(&'a self, info: &'a P) -> Option<BotInfo<'a>>
+    where
+        P: UserAgentInformation + ?Sized,
+    {
+        if let Some(bot) = self.lookup_user_agent(info.user_agent()) {
+            return Some(bot);
+        }
+        if !info.is_bot() {
+            return None;
+        }
+        Some(BotInfo {
+            name: Cow::Borrowed(&info.browser().family),
+            operator: None,
+            category: BotCategory::Other,
+        })
+    }
+
+    /// Returns the entry whose token occurs in `user_agent`.
+    pub(crate) fn find(&self, user_agent: &str) -> Option<&BotEntry> {
+        self.matcher()
+            .find_overlapping_iter(user_agent)
+            .map(|m| m.pattern().as_usize())
+            .max_by_key(|&i| (self.entries[i].token.len(), i))
+            .map(|i| &self.entries[i])
+    }
+
+    fn matcher(&self) -> &AhoCorasick {
+        self.matcher.get_or_init(|| {
+            AhoCorasick::builder()
+                .match_kind(MatchKind::Standard)
+                .ascii_case_insensitive(true)
+                .build(self.entries.iter().map(|entry| &entry.token))
+                .expect("bot tokens exceed the automaton size limits")
+        })
+    }
+}
+
+impl Extend<BotEntry> for BotTable {
+    fn extend<I: IntoIterator<Item = BotEntry>>(&mut self, entries: I) {
+        self.entries.extend(entries);
+        self.matcher = OnceCell::INIT;
+    }
+}
+
+/// Looks up `info` in the built-in `BotTable`.
+pub(crate) fn builtin_lookup<P>(info: &P) -> Option<BotInfo<'_>>
+where
+    P: UserAgentInformation + ?Sized,
+{
+    BUILTIN.lookup(info)
+}
+
+#[test]
+fn test_bot_table() {
+    use crate::unsync::BorrowingParser as Parser;
+
+    let cases = [
+        ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", "Googlebot", BotCategory::SearchEngine),
+        ("Googlebot-Image/1.0", "Googlebot-Image", BotCategory::SearchEngine),
+        ("Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; GPTBot/1.2; +https://openai.com/gptbot)", "GPTBot", BotCategory::AiCrawler),
+        ("facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)", "facebookexternalhit", BotCategory::SocialPreview),
+        ("Mozilla/5.0 (compatible; AhrefsBot/7.0; +http://ahrefs.com/robot/)", "AhrefsBot", BotCategory::Seo),
+        ("curl/7.64.1", "curl", BotCategory::HttpLibrary),
+        ("Mozilla/5.0 (compatible; Nmap Scripting Engine; https://nmap.org/book/nse.html)", "Nmap", BotCategory::SecurityScanner),
+    ];
+    for &(agent, name, category) in cases.iter() {
+        let parser = Parser::new(agent);
+        let bot = parser.bot_info().unwrap();
+        assert_eq!((&bot.name[..], bot.category), (name, category), "{}", agent);
+    }
+    let firefox =
+        Parser::new("Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0");
+    assert_eq!(firefox.bot_info(), None);
+    // Recognized by the rules, but not in the table
+    let spider = Parser::new(cases[0].0);
+    let empty = BotTable::new();
+    let bot = empty.lookup(&spider).unwrap();
+    assert_eq!(
+        (&bot.name[..], bot.category),
+        ("Googlebot", BotCategory::Other)
+    );
+
+    let mut bots = BotTable::builtin();
+    bots.extend_yaml(
+        "- token: curl/\n  name: Our Health Check\n  operator: Us\n  category: monitoring\n",
+    )
+    .unwrap();
+    let bot = bots.lookup_user_agent("curl/8.0").unwrap();
+    assert_eq!(bot.name, "Our Health Check");
+    assert_eq!(bot.category, BotCategory::Monitoring);
+    assert!(bots
+        .extend_yaml("- token: x\n  name: X\n  category: unknown\n")
+        .is_err());
+}
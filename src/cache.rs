@@ -0,0 +1,209 @@
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+
+use crate::sync::OwningParser;
+use crate::{Browser, Device, RegexDatabase, OS};
+
+/// Estimated memory used by an entry in addition to the user agent.
+const ENTRY_OVERHEAD: usize = mem::size_of::<Arc<str>>()
+    + mem::size_of::<OwningParser>()
+    + mem::size_of::<Browser>()
+    + mem::size_of::<Device>()
+    + mem::size_of::<OS>();
+
+/// A bounded cache of parsers keyed by the user agent string.
+///
+/// Repeated user agents share one `OwningParser`, so the browser, device and
+/// operating system of each user agent are parsed at most once while it is
+/// cached. Parsers are still lazy, only the information requested is parsed.
+///
+/// When the cache is full the least recently used user agent is evicted. The
+/// size is limited by the number of entries and optionally by the memory used,
+/// which is estimated from the length of the user agents.
+///
+/// ```rust
+/// use uap_rust::sync::ParserCache;
+/// let cache = ParserCache::new(10_000);
+/// let parser = cache.get("Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0");
+/// assert_eq!(parser.browser().family, "Firefox");
+/// ```
+pub struct ParserCache {
+    db: Arc<RegexDatabase>,
+    max_entries: usize,
+    max_bytes: Option<usize>,
+    state: Mutex<State>,
+}
+
+struct State {
+    parsers: LruCache<Arc<str>, Arc<OwningParser>>,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+/// Statistics about the usage of a `ParserCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of lookups which found a cached parser.
+    pub hits: u64,
+    /// Number of lookups which created a new parser.
+    pub misses: u64,
+    /// Number of cached user agents.
+    pub entries: usize,
+    /// Estimated memory used by the cached entries in bytes.
+    pub bytes: usize,
+}
+
+impl CacheStats {
+    /// Returns the share of lookups answered from the cache.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl ParserCache {
+    /// Creates a cache holding at most `max_entries` user agents.
+    pub fn new(max_entries: usize) -> Self {
+        Self::with_database(max_entries, RegexDatabase::builtin())
+    }
+
+    /// Creates a cache whose parsers use the rules of `db`.
+    pub fn with_database(max_entries: usize, db: Arc<RegexDatabase>) -> Self {
+        ParserCache {
+            db,
+            max_entries,
+            max_bytes: None,
+            state: Mutex::new(State {
+                parsers: LruCache::unbounded(),
+                bytes: 0,
+                hits: 0,
+                misses: 0,
+            }),
+        }
+    }
+
+    /// Additionally limits the estimated memory used by the cache.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Returns the parser for `user_agent`, creating it on a cache miss.
+    pub fn get(&self, user_agent: &str) -> Arc<OwningParser> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(parser) = state.parsers.get(user_agent) {
+            let parser = parser.clone();
+            state.hits += 1;
+            return parser;
+        }
+        state.misses += 1;
+
+        let user_agent: Arc<str> = Arc::from(user_agent);
+        let parser = Arc::new(OwningParser::with_database(
+            user_agent.clone(),
+            self.db.clone(),
+        ));
+        let size = entry_size(&user_agent);
+        if self.max_entries == 0 || self.max_bytes.is_some_and(|max| size > max) {
+            return parser;
+        }
+        while state.parsers.len() >= self.max_entries
+            || self.max_bytes.is_some_and(|max| state.bytes + size > max)
+        {
+            match state.parsers.pop_lru() {
+                Some((evicted, _)) => state.bytes -= entry_size(&evicted),
+                None => break,
+            }
+        }
+        state.bytes += size;
+        state.parsers.put(user_agent, parser.clone());
+        parser
+    }
+
+    /// Returns the hit and miss counts and the current size of the cache.
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        CacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.parsers.len(),
+            bytes: state.bytes,
+        }
+    }
+
+    /// Removes all cached parsers. The statistics are kept.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.parsers.clear();
+        state.bytes = 0;
+    }
+}
+
+fn entry_size(user_agent: &str) -> usize {
+    user_agent.len() + ENTRY_OVERHEAD
+}
+
+#[test]
+fn test_hits_and_misses() {
+    let cache = ParserCache::new(10);
+    let first = cache.get("Firefox/60.0");
+    let second = cache.get("Firefox/60.0");
+    assert!(Arc::ptr_eq(&first, &second));
+    cache.get("Chrome/70.0");
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+    assert_eq!(
+        stats.bytes,
+        entry_size("Firefox/60.0") + entry_size("Chrome/70.0")
+    );
+}
+
+#[test]
+fn test_eviction() {
+    let cache = ParserCache::new(2);
+    cache.get("a");
+    cache.get("b");
+    cache.get("a");
+    cache.get("c");
+    assert_eq!(cache.stats().entries, 2);
+    cache.get("a");
+    assert_eq!(cache.stats().misses, 3);
+    cache.get("b");
+    assert_eq!(cache.stats().misses, 4);
+
+    let cache = ParserCache::new(100).max_bytes(2 * entry_size("ab"));
+    cache.get("ab");
+    cache.get("cd");
+    cache.get("ef");
+    assert_eq!(cache.stats().entries, 2);
+    cache.get("abcdefgh");
+    assert_eq!(cache.stats().entries, 1);
+    assert_eq!(cache.stats().bytes, entry_size("abcdefgh"));
+}
+
+#[test]
+fn test_shared_between_threads() {
+    use crate::UserAgentInformation;
+    use std::thread;
+
+    let cache = Arc::new(ParserCache::new(10));
+    let agent = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let cache = cache.clone();
+            thread::spawn(move || cache.get(agent).is_bot())
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.join().unwrap());
+    }
+    assert_eq!(cache.stats().misses, 1);
+    assert_eq!(cache.stats().hits, 3);
+}
@@ -0,0 +1,572 @@
+//! User-Agent Client Hints.
+//!
+//! Chromium based browsers reduce the information in the user agent string,
+//! e.g. the minor version of the browser is always `0` and Windows 11 reports
+//! itself as Windows 10. The full information is sent in the `Sec-CH-UA-*`
+//! headers instead. `ClientHints` parses these headers and combines them with
+//! the information parsed from the user agent:
+//!
+//! ```rust
+//! use uap_rust::client_hints::{ClientHints, Field, Source};
+//! use uap_rust::unsync::BorrowingParser as Parser;
+//!
+//! let parser = Parser::new("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Safari/537.36");
+//! let hints = ClientHints::from_headers(vec![
+//!     ("Sec-CH-UA-Full-Version-List", r#""Chromium";v="110.0.5481.100", "Not A(Brand";v="24.0.0.0", "Google Chrome";v="110.0.5481.100""#),
+//!     ("Sec-CH-UA-Platform", r#""Windows""#),
+//!     ("Sec-CH-UA-Platform-Version", r#""15.0.0""#),
+//! ]);
+//! let info = hints.apply(&parser);
+//! assert_eq!(info.os.family, "Windows");
+//! assert_eq!(info.os.major.as_ref().unwrap(), "11");
+//! assert_eq!(info.browser.patch.as_ref().unwrap(), "5481");
+//! assert_eq!(info.source(Field::OSMajor), Source::ClientHints);
+//! assert_eq!(info.source(Field::BrowserFamily), Source::UserAgent);
+//! ```
+use std::borrow::Cow;
+
+use crate::{Browser, Device, UserAgentInformation, DEFAULT_NAME, OS};
+
+/// Brand and version from the `Sec-CH-UA` or `Sec-CH-UA-Full-Version-List`
+/// header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Brand {
+    pub brand: String,
+    pub version: String,
+}
+
+/// The parsed `Sec-CH-UA-*` request headers.
+///
+/// Headers which are missing or malformed are `None` or empty respectively.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHints {
+    /// `Sec-CH-UA`, brands with their major version.
+    pub brands: Vec<Brand>,
+    /// `Sec-CH-UA-Full-Version-List`, brands with their full version.
+    pub full_version_list: Vec<Brand>,
+    /// `Sec-CH-UA-Platform`, e.g. `Windows` or `macOS`.
+    pub platform: Option<String>,
+    /// `Sec-CH-UA-Platform-Version`.
+    pub platform_version: Option<String>,
+    /// `Sec-CH-UA-Model`, empty for desktop devices.
+    pub model: Option<String>,
+    /// `Sec-CH-UA-Mobile`.
+    pub mobile: Option<bool>,
+}
+
+/// Where the value of a field of a `HintedUserAgent` comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    UserAgent,
+    ClientHints,
+}
+
+/// A field of the browser, operating system or device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    BrowserFamily,
+    BrowserMajor,
+    BrowserMinor,
+    BrowserPatch,
+    OSFamily,
+    OSMajor,
+    OSMinor,
+    OSPatch,
+    OSPatchMinor,
+    DeviceFamily,
+    DeviceBrand,
+    DeviceModel,
+}
+
+/// Information parsed from a user agent combined with client hints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintedUserAgent<'a> {
+    pub browser: Browser<'a>,
+    pub os: OS<'a>,
+    pub device: Device<'a>,
+    /// Whether the browser prefers a mobile experience, from
+    /// `Sec-CH-UA-Mobile`.
+    pub mobile: Option<bool>,
+    hinted: Vec<Field>,
+}
+
+impl<'a> HintedUserAgent<'a> {
+    /// Returns where the value of `field` comes from.
+    pub fn source(&self, field: Field) -> Source {
+        if self.hinted.contains(&field) {
+            Source::ClientHints
+        } else {
+            Source::UserAgent
+        }
+    }
+
+    /// Returns the fields whose value comes from the client hints.
+    pub fn hinted_fields(&self) -> &[Field] {
+        &self.hinted
+    }
+
+    fn set<T>(&mut self, field: Field, value: Option<T>)
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        let value = value.map(Into::into);
+        match field {
+            Field::BrowserFamily => self.browser.family = value.unwrap_or(DEFAULT_NAME.into()),
+            Field::BrowserMajor => self.browser.major = value,
+            Field::BrowserMinor => self.browser.minor = value,
+            Field::BrowserPatch => self.browser.patch = value,
+            Field::OSFamily => self.os.family = value.unwrap_or(DEFAULT_NAME.into()),
+            Field::OSMajor => self.os.major = value,
+            Field::OSMinor => self.os.minor = value,
+            Field::OSPatch => self.os.patch = value,
+            Field::OSPatchMinor => self.os.patch_minor = value,
+            Field::DeviceFamily => self.device.family = value.unwrap_or(DEFAULT_NAME.into()),
+            Field::DeviceBrand => self.device.brand = value,
+            Field::DeviceModel => self.device.model = value,
+        }
+        if !self.hinted.contains(&field) {
+            self.hinted.push(field);
+        }
+    }
+}
+
+/// Brand names in client hints and the corresponding uap-core family.
+static BRAND_FAMILIES: &[(&str, &str)] = &[
+    ("Google Chrome", "Chrome"),
+    ("Microsoft Edge", "Edge"),
+    ("Opera", "Opera"),
+    ("Yandex", "Yandex Browser"),
+    ("Samsung Internet", "Samsung Internet"),
+    ("Chromium", "Chromium"),
+];
+
+/// Platforms in client hints and the corresponding uap-core family.
+static PLATFORM_FAMILIES: &[(&str, &str)] = &[
+    ("Windows", "Windows"),
+    ("macOS", "Mac OS X"),
+    ("Android", "Android"),
+    ("Chrome OS", "Chrome OS"),
+    ("Chromium OS", "Chrome OS"),
+    ("Linux", "Linux"),
+    ("iOS", "iOS"),
+];
+
+fn lookup<'t>(table: &[(&str, &'t str)], name: &'t str) -> &'t str {
+    table
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(name, |(_, family)| family)
+}
+
+/// Brands like `Not A(Brand` are added to prevent exact matching.
+fn is_grease(brand: &str) -> bool {
+    brand.contains("Not") && brand.contains("Brand")
+}
+
+impl ClientHints {
+    /// Parses the client hints in `headers`, given as pairs of the header
+    /// name and value. Other headers are ignored.
+    pub fn from_headers<'h, I>(headers: I) -> Self
+    where
+        I: IntoIterator<Item = (&'h str, &'h str)>,
+    {
+        let mut hints = ClientHints::default();
+        for (name, value) in headers {
+            hints.set(name, value);
+        }
+        hints
+    }
+
+    /// Parses the value of the client hint header `name`. Returns `false`
+    /// if the header is no client hint or its value is malformed.
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        let value = value.trim();
+        let name = name.to_ascii_lowercase();
+        let parsed = match &name[..] {
+            "sec-ch-ua" => parse_brands(value).map(|b| self.brands = b),
+            "sec-ch-ua-full-version-list" => {
+                parse_brands(value).map(|b| self.full_version_list = b)
+            }
+            "sec-ch-ua-platform" => parse_string(value).map(|s| self.platform = Some(s)),
+            "sec-ch-ua-platform-version" => {
+                parse_string(value).map(|s| self.platform_version = Some(s))
+            }
+            "sec-ch-ua-model" => parse_string(value).map(|s| self.model = Some(s)),
+            "sec-ch-ua-mobile" => match parse_bare_item(&mut Input::new(value)) {
+                Some(Item::Boolean(mobile)) => {
+                    self.mobile = Some(mobile);
+                    Some(())
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        parsed.is_some()
+    }
+
+    /// Combines the information parsed from the user agent with the client
+    /// hints, which take precedence.
+    pub fn apply<'a, P>(&'a self, parser: &'a P) -> HintedUserAgent<'a>
+    where
+        P: UserAgentInformation + ?Sized,
+    {
+        let mut info = HintedUserAgent {
+            browser: parser.browser().clone(),
+            os: parser.os().clone(),
+            device: parser.device().clone(),
+            mobile: self.mobile,
+            hinted: Vec::new(),
+        };
+        self.apply_browser(&mut info);
+        self.apply_os(&mut info);
+        self.apply_device(&mut info);
+        info
+    }
+
+    fn apply_browser<'a>(&'a self, info: &mut HintedUserAgent<'a>) {
+        let (brands, full) = if self.full_version_list.is_empty() {
+            (&self.brands, false)
+        } else {
+            (&self.full_version_list, true)
+        };
+        let mut brands = brands.iter().filter(|b| !is_grease(&b.brand));
+        // Chromium is listed along the actual browser built on it
+        let brand = match brands.clone().find(|b| b.brand != "Chromium") {
+            Some(brand) => brand,
+            None => match brands.next() {
+                Some(brand) => brand,
+                None => return,
+            },
+        };
+        let family = lookup(BRAND_FAMILIES, &brand.brand);
+        let same_family = info.browser.family.starts_with(family);
+        if !same_family {
+            info.set(Field::BrowserFamily, Some(family));
+        }
+        let mut version = brand.version.split('.').filter(|v| !v.is_empty());
+        let major = version.next();
+        if full {
+            info.set(Field::BrowserMajor, major);
+            info.set(Field::BrowserMinor, version.next());
+            info.set(Field::BrowserPatch, version.next());
+        } else if !same_family || info.browser.major.as_deref() != major {
+            info.set(Field::BrowserMajor, major);
+            info.set(Field::BrowserMinor, None::<&str>);
+            info.set(Field::BrowserPatch, None::<&str>);
+        }
+    }
+
+    fn apply_os<'a>(&'a self, info: &mut HintedUserAgent<'a>) {
+        let platform = match self.platform.as_deref() {
+            Some("") | Some("Unknown") | None => return,
+            Some(platform) => platform,
+        };
+        let family = lookup(PLATFORM_FAMILIES, platform);
+        let same_family = info.os.family.starts_with(family);
+        if !same_family {
+            info.set(Field::OSFamily, Some(family));
+        }
+        let mut version = self
+            .platform_version
+            .as_deref()
+            .unwrap_or("")
+            .split('.')
+            .filter(|v| !v.is_empty());
+        let major = version.next();
+        if family == "Windows" {
+            // The platform version is the version of the Windows API
+            let major = match major.and_then(|m| m.parse::<u32>().ok()) {
+                Some(0) | None if same_family => return,
+                Some(major) if major >= 13 => Some("11"),
+                Some(major) if major > 0 => Some("10"),
+                _ => None,
+            };
+            // The rest of the API version is not part of the Windows version
+            info.set(Field::OSMajor, major);
+            info.set(Field::OSMinor, None::<&str>);
+            info.set(Field::OSPatch, None::<&str>);
+            info.set(Field::OSPatchMinor, None::<&str>);
+            return;
+        } else if major.is_some() || !same_family {
+            info.set(Field::OSMajor, major);
+            info.set(Field::OSMinor, version.next());
+        } else {
+            return;
+        }
+        info.set(Field::OSPatch, version.next());
+        info.set(Field::OSPatchMinor, None::<&str>);
+    }
+
+    fn apply_device<'a>(&'a self, info: &mut HintedUserAgent<'a>) {
+        let model = match self.model.as_deref() {
+            Some("") | None => return,
+            Some(model) => model,
+        };
+        // Reduced user agents only contain the model `K`
+        let unknown = info.device.family == DEFAULT_NAME
+            || info.device.family.starts_with("Generic")
+            || info.device.family == "K";
+        if !unknown {
+            return;
+        }
+        info.set(Field::DeviceFamily, Some(model));
+        info.set(Field::DeviceModel, Some(model));
+        if info.device.brand.as_deref() == Some("Generic") {
+            info.set(Field::DeviceBrand, None::<&str>);
+        }
+    }
+}
+
+/// A bare item of a structured header field, see RFC 8941.
+#[derive(Debug, PartialEq)]
+enum Item {
+    String(String),
+    Token(String),
+    Number(String),
+    Boolean(bool),
+}
+
+type Parameters = Vec<(String, Item)>;
+
+struct Input<'s> {
+    rest: &'s str,
+}
+
+impl<'s> Input<'s> {
+    fn new(input: &'s str) -> Self {
+        Input { rest: input }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.rest = &self.rest[c.len_utf8()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'s str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or(self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(|c| c == ' ' || c == '\t');
+    }
+}
+
+fn parse_bare_item(input: &mut Input<'_>) -> Option<Item> {
+    match input.peek()? {
+        '"' => {
+            input.eat('"');
+            let mut value = String::new();
+            loop {
+                let c = input.peek()?;
+                input.eat(c);
+                match c {
+                    '"' => return Some(Item::String(value)),
+                    '\\' => {
+                        let escaped = input.peek().filter(|&c| c == '"' || c == '\\')?;
+                        input.eat(escaped);
+                        value.push(escaped);
+                    }
+                    c if c.is_ascii() && !c.is_ascii_control() => value.push(c),
+                    _ => return None,
+                }
+            }
+        }
+        '?' => {
+            input.eat('?');
+            if input.eat('1') {
+                Some(Item::Boolean(true))
+            } else if input.eat('0') {
+                Some(Item::Boolean(false))
+            } else {
+                None
+            }
+        }
+        c if c == '-' || c.is_ascii_digit() => {
+            let number = input.take_while(|c| c == '-' || c == '.' || c.is_ascii_digit());
+            Some(Item::Number(number.into()))
+        }
+        c if c == '*' || c.is_ascii_alphabetic() => {
+            let token =
+                input.take_while(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~:/".contains(c));
+            Some(Item::Token(token.into()))
+        }
+        _ => None,
+    }
+}
+
+/// Parses the parameters of an item and returns them as key value pairs.
+fn parse_parameters(input: &mut Input<'_>) -> Option<Parameters> {
+    let mut parameters = Vec::new();
+    while input.eat(';') {
+        input.skip_whitespace();
+        let key = input
+            .take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-.*".contains(c));
+        if key.is_empty() {
+            return None;
+        }
+        let value = if input.eat('=') {
+            parse_bare_item(input)?
+        } else {
+            Item::Boolean(true)
+        };
+        parameters.push((key.into(), value));
+    }
+    Some(parameters)
+}
+
+/// Parses a list of items with their parameters. Inner lists are not
+/// supported.
+fn parse_list(value: &str) -> Option<Vec<(Item, Parameters)>> {
+    let mut input = Input::new(value);
+    let mut list = Vec::new();
+    input.skip_whitespace();
+    while input.peek().is_some() {
+        let item = parse_bare_item(&mut input)?;
+        let parameters = parse_parameters(&mut input)?;
+        list.push((item, parameters));
+        input.skip_whitespace();
+        if input.peek().is_none() {
+            break;
+        }
+        if !input.eat(',') {
+            return None;
+        }
+        input.skip_whitespace();
+        input.peek()?;
+    }
+    Some(list)
+}
+
+fn parse_string(value: &str) -> Option<String> {
+    let mut input = Input::new(value);
+    match parse_bare_item(&mut input)? {
+        Item::String(s) => {
+            parse_parameters(&mut input)?;
+            Some(s).filter(|_| input.rest.is_empty())
+        }
+        _ => None,
+    }
+}
+
+fn parse_brands(value: &str) -> Option<Vec<Brand>> {
+    parse_list(value)?
+        .into_iter()
+        .map(|(item, parameters)| {
+            let brand = match item {
+                Item::String(brand) => brand,
+                _ => return None,
+            };
+            let version = parameters
+                .into_iter()
+                .find(|(key, _)| key == "v")
+                .and_then(|(_, value)| match value {
+                    Item::String(v) | Item::Token(v) | Item::Number(v) => Some(v),
+                    Item::Boolean(_) => None,
+                })
+                .unwrap_or_default();
+            Some(Brand { brand, version })
+        })
+        .collect()
+}
+
+#[test]
+fn test_structured_fields() {
+    let brand = |brand: &str, version: &str| Brand {
+        brand: brand.into(),
+        version: version.into(),
+    };
+    assert_eq!(
+        parse_brands(r#""Chromium";v="110", "Not A(Brand";v="24" , "Google Chrome";v="110""#),
+        Some(vec![
+            brand("Chromium", "110"),
+            brand("Not A(Brand", "24"),
+            brand("Google Chrome", "110"),
+        ])
+    );
+    assert_eq!(
+        parse_brands(r#""\"Quoted\\";v=1;x"#),
+        Some(vec![brand("\"Quoted\\", "1")])
+    );
+    assert_eq!(parse_brands(r#""Chromium";v="110","#), None);
+    assert_eq!(parse_brands(""), Some(vec![]));
+    assert_eq!(parse_string(r#""Windows""#), Some("Windows".into()));
+    assert_eq!(parse_string(r#""Windows" x"#), None);
+    assert_eq!(parse_string("Windows"), None);
+
+    let mut hints = ClientHints::default();
+    assert!(hints.set("sec-ch-ua-mobile", "?1"));
+    assert!(!hints.set("Sec-CH-UA-Mobile", "1"));
+    assert!(!hints.set("Accept", "*/*"));
+    assert_eq!(hints.mobile, Some(true));
+}
+
+#[test]
+fn test_apply() {
+    use crate::unsync::BorrowingParser as Parser;
+
+    let parser = Parser::new("Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Mobile Safari/537.36");
+    let hints = ClientHints::from_headers(vec![
+        (
+            "Sec-CH-UA",
+            r#""Chromium";v="110", "Not A(Brand";v="24", "Google Chrome";v="110""#,
+        ),
+        ("Sec-CH-UA-Mobile", "?1"),
+        ("Sec-CH-UA-Platform", r#""Android""#),
+        ("Sec-CH-UA-Platform-Version", r#""13.0.0""#),
+        ("Sec-CH-UA-Model", r#""Pixel 7""#),
+    ]);
+    let info = hints.apply(&parser);
+    assert_eq!(info.browser, parser.browser().clone());
+    assert_eq!(info.os.major.as_deref(), Some("13"));
+    assert_eq!(info.device.family, "Pixel 7");
+    assert_eq!(info.device.brand, None);
+    assert_eq!(info.mobile, Some(true));
+    assert_eq!(info.source(Field::BrowserMajor), Source::UserAgent);
+    assert_eq!(info.source(Field::OSFamily), Source::UserAgent);
+    assert_eq!(info.source(Field::OSMajor), Source::ClientHints);
+    assert_eq!(info.source(Field::DeviceModel), Source::ClientHints);
+
+    let parser = Parser::new("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Safari/537.36 Edg/110.0.1587.57");
+    let hints = ClientHints::from_headers(vec![
+        (
+            "sec-ch-ua-full-version-list",
+            r#""Chromium";v="110.0.5481.178", "Not A(Brand";v="24.0.0.0", "Microsoft Edge";v="110.0.1587.57""#,
+        ),
+        ("sec-ch-ua-platform", r#""Windows""#),
+        ("sec-ch-ua-platform-version", r#""10.0.0""#),
+        ("sec-ch-ua-model", r#""""#),
+    ]);
+    let info = hints.apply(&parser);
+    assert_eq!(info.browser.family, "Edge");
+    assert_eq!(info.browser.patch.as_deref(), Some("1587"));
+    assert_eq!(info.os.family, "Windows");
+    assert_eq!(info.os.major.as_deref(), Some("10"));
+    assert_eq!(info.os.patch, None);
+    assert_eq!(info.device.family, "Other");
+    assert_eq!(info.source(Field::BrowserFamily), Source::ClientHints);
+    assert_eq!(info.source(Field::DeviceFamily), Source::UserAgent);
+
+    let hints = ClientHints::from_headers(vec![
+        ("sec-ch-ua-platform", r#""Windows""#),
+        ("sec-ch-ua-platform-version", r#""15.0.0""#),
+    ]);
+    let info = hints.apply(&parser);
+    assert_eq!(info.os.major.as_deref(), Some("11"));
+    assert_eq!((&info.os.minor, &info.os.patch), (&None, &None));
+
+    let hints = ClientHints::default();
+    let info = hints.apply(&parser);
+    assert!(info.hinted_fields().is_empty());
+    assert_eq!(info.browser, parser.browser().clone());
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{UserAgentInformation, DEFAULT_NAME};
+
+/// `DeviceType` is the form factor of the device.
+///
+/// It is derived from the parsed browser, device and operating system and
+/// from tokens in the user agent like `Mobile` or `SmartTV`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DeviceType {
+    Desktop,
+    Phone,
+    Tablet,
+    TV,
+    Console,
+    Wearable,
+    Car,
+    Bot,
+    Unknown,
+}
+
+impl DeviceType {
+    /// Returns the name of the device type in lower case, e.g. `"phone"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceType::Desktop => "desktop",
+            DeviceType::Phone => "phone",
+            DeviceType::Tablet => "tablet",
+            DeviceType::TV => "tv",
+            DeviceType::Console => "console",
+            DeviceType::Wearable => "wearable",
+            DeviceType::Car => "car",
+            DeviceType::Bot => "bot",
+            DeviceType::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// All tokens are lower case and matched ignoring ASCII case.
+
+static CAR_TOKENS: &[&str] = &["tesla", "qtcarbrowser", "carplay", "android automotive"];
+
+static CONSOLE_TOKENS: &[&str] = &["playstation", "xbox", "nintendo", "ouya"];
+
+static TV_TOKENS: &[&str] = &[
+    "smarttv",
+    "smart-tv",
+    "smart tv",
+    "hbbtv",
+    "googletv",
+    "google tv",
+    "android tv",
+    "appletv",
+    "apple tv",
+    "crkey",
+    "roku",
+    "netcast",
+    "web0s",
+    "webos.tv",
+    "bravia",
+    "inettv",
+    "webtv",
+    "; aft",
+];
+
+static WEARABLE_TOKENS: &[&str] = &[
+    "watchos",
+    "wearos",
+    "wear os",
+    "smartwatch",
+    "apple watch",
+    "galaxy watch",
+];
+
+static TABLET_TOKENS: &[&str] = &["ipad", "kindle", "silk/", "playbook"];
+
+static PHONE_TOKENS: &[&str] = &[
+    "iphone",
+    "ipod",
+    "smartphone",
+    "mobi",
+    "opera mini",
+    "midp",
+    "j2me",
+    "symbian",
+    "blackberry",
+    "kaios",
+];
+
+static MOBILE_OS: &[&str] = &[
+    "Android",
+    "iOS",
+    "Windows Phone",
+    "Windows Mobile",
+    "BlackBerry OS",
+    "Symbian OS",
+    "Firefox OS",
+    "KaiOS",
+];
+
+static DESKTOP_OS: &[&str] = &[
+    "Windows",
+    "Mac OS X",
+    "Linux",
+    "Ubuntu",
+    "Kubuntu",
+    "Debian",
+    "Fedora",
+    "Red Hat",
+    "CentOS",
+    "SUSE",
+    "openSUSE",
+    "Mandriva",
+    "Gentoo",
+    "Slackware",
+    "Mint",
+    "Arch Linux",
+    "Chrome OS",
+    "FreeBSD",
+    "OpenBSD",
+    "NetBSD",
+    "Solaris",
+];
+
+fn contains_ci(haystack: &str, needle: &str) -> bool {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .any(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn contains_any(haystack: &str, needles: &[&str]) -> bool {
+    needles.iter().any(|needle| contains_ci(haystack, needle))
+}
+
+/// Returns `true` if `word` occurs in `haystack` surrounded by non
+/// alphanumeric characters.
+fn has_word(haystack: &str, word: &str) -> bool {
+    haystack
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|w| w.eq_ignore_ascii_case(word))
+}
+
+/// Returns `true` if the word `tablet` occurs in `haystack`, except in the
+/// `Tablet PC` token of Windows desktops with pen input.
+fn has_tablet(haystack: &str) -> bool {
+    let mut words = haystack
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .peekable();
+    while let Some(word) = words.next() {
+        if word.eq_ignore_ascii_case("tablet")
+            && !words
+                .peek()
+                .is_some_and(|next| next.eq_ignore_ascii_case("pc"))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+pub(crate) fn classify<P: UserAgentInformation + ?Sized>(info: &P) -> DeviceType {
+    let agent = info.user_agent();
+    let device = info.device();
+    if device.family == "Spider" {
+        return DeviceType::Bot;
+    }
+    let family = &device.family[..];
+    if contains_any(agent, CAR_TOKENS) || contains_ci(family, "tesla") {
+        return DeviceType::Car;
+    }
+    if contains_any(agent, CONSOLE_TOKENS) || contains_any(family, CONSOLE_TOKENS) {
+        return DeviceType::Console;
+    }
+    if contains_any(agent, TV_TOKENS) || has_word(family, "tv") || has_word(agent, "tv") {
+        return DeviceType::TV;
+    }
+    let os = &info.os().family[..];
+    if contains_any(agent, WEARABLE_TOKENS) || has_word(family, "watch") || os == "watchOS" {
+        return DeviceType::Wearable;
+    }
+    match family {
+        "iPhone" | "iPod" | "Generic Feature Phone" => return DeviceType::Phone,
+        "iPad" | "Generic Tablet" => return DeviceType::Tablet,
+        _ => (),
+    }
+    if contains_any(agent, TABLET_TOKENS)
+        || contains_any(family, TABLET_TOKENS)
+        || has_tablet(agent)
+        || has_tablet(family)
+        || has_word(family, "tab")
+        || has_word(family, "pad")
+    {
+        return DeviceType::Tablet;
+    }
+    // Android browsers on tablets omit the `Mobile` token. uap-core reports
+    // them as `Generic Smartphone` if the model is unknown.
+    if os == "Android"
+        && agent.starts_with("Mozilla/")
+        && !has_word(agent, "mobile")
+        && !contains_ci(agent, "opera mini")
+    {
+        return DeviceType::Tablet;
+    }
+    let browser = &info.browser().family[..];
+    if contains_any(agent, PHONE_TOKENS)
+        || has_word(agent, "phone")
+        || MOBILE_OS.contains(&os)
+        || browser.contains("Mobile")
+        || browser.contains("Mini")
+    {
+        return DeviceType::Phone;
+    }
+    let desktop_os = DESKTOP_OS.iter().any(|d| os.starts_with(d));
+    if desktop_os && (family == DEFAULT_NAME || family == "Mac") {
+        return DeviceType::Desktop;
+    }
+    // Most devices known to uap-core are phones
+    if family != DEFAULT_NAME {
+        return DeviceType::Phone;
+    }
+    DeviceType::Unknown
+}
+
+#[test]
+fn test_classify() {
+    use crate::unsync::BorrowingParser as Parser;
+
+    let cases = [
+        ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Safari/537.36", DeviceType::Desktop),
+        ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.3 Safari/605.1.15", DeviceType::Desktop),
+        ("Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0", DeviceType::Desktop),
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 5_1_1 like Mac OS X) AppleWebKit/534.46 (KHTML, like Gecko) Version/5.1 Mobile/9B206 Safari/7534.48.3", DeviceType::Phone),
+        ("Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Mobile Safari/537.36", DeviceType::Phone),
+        ("Mozilla/5.0 (Linux; Android 12; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Safari/537.36", DeviceType::Tablet),
+        ("Mozilla/5.0 (iPad; CPU OS 12_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148", DeviceType::Tablet),
+        ("Mozilla/5.0 (SMART-TV; Linux; Tizen 5.0) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/2.2 Chrome/63.0.3239.84 TV Safari/537.36", DeviceType::TV),
+        ("Mozilla/5.0 (Linux; U; Android 4.2.2; en-us; HbbTV/1.2.1 (;;;;;)) AppleWebKit/534.30 (KHTML, like Gecko) Version/4.0 Safari/534.30", DeviceType::TV),
+        ("Mozilla/5.0 (PlayStation 4 3.11) AppleWebKit/537.73 (KHTML, like Gecko)", DeviceType::Console),
+        ("Mozilla/5.0 (X11; GNU/Linux) AppleWebKit/601.1 (KHTML, like Gecko) Tesla QtCarBrowser Safari/601.1", DeviceType::Car),
+        ("Mozilla/5.0 (Linux; Android 11; Galaxy Watch4) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Mobile Safari/537.36", DeviceType::Wearable),
+        ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", DeviceType::Bot),
+        ("Mozilla/5.0 (Windows NT 6.1; WOW64; Trident/7.0; SLCC2; .NET CLR 2.0.50727; Media Center PC 6.0; Tablet PC 2.0; rv:11.0) like Gecko", DeviceType::Desktop),
+        ("Mozilla/5.0 (Android 4.4; Tablet; rv:41.0) Gecko/41.0 Firefox/41.0", DeviceType::Tablet),
+        ("Mozilla/5.0 (Windows Phone 10.0; Android 6.0.1; Microsoft; Lumia 950) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/52.0.2743.116 Mobile Safari/537.36 Edge/15.15063", DeviceType::Phone),
+        ("curl/7.64.1", DeviceType::Unknown),
+    ];
+    for &(agent, expected) in cases.iter() {
+        assert_eq!(Parser::new(agent).device_type(), expected, "{}", agent);
+    }
+    let parser = Parser::new(cases[5].0);
+    assert!(parser.is_tablet() && !parser.is_mobile() && !parser.is_desktop());
+}
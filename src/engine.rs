@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+
+use crate::Engine;
+
+/// First version of Chrome using Blink instead of WebKit.
+const FIRST_BLINK_CHROME: u32 = 28;
+
+/// Returns the version following `token` in `agent`, e.g. `7.0` for
+/// `Trident/` in `... Trident/7.0; rv:11.0) like Gecko`.
+pub(crate) fn token_version<'a>(agent: &'a str, token: &str) -> Option<&'a str> {
+    let start = agent.find(token)? + token.len();
+    let rest = &agent[start..];
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    Some(&rest[..end]).filter(|v| !v.is_empty())
+}
+
+fn engine<'a>(family: &'static str, version: Option<&'a str>) -> Engine<'a> {
+    let mut parts = version
+        .unwrap_or("")
+        .split('.')
+        .map(|part| Some(part).filter(|p| !p.is_empty()).map(Cow::Borrowed));
+    Engine {
+        family: family.into(),
+        major: parts.next().flatten(),
+        minor: parts.next().flatten(),
+        patch: parts.next().flatten(),
+    }
+}
+
+pub(crate) fn is_ios(agent: &str) -> bool {
+    agent.contains("like Mac OS X")
+        && (agent.contains("iPhone") || agent.contains("iPad") || agent.contains("iPod"))
+}
+
+pub(crate) fn parse_engine(agent: &str) -> Engine<'_> {
+    // Chromium based Edge uses `Edg/`
+    if agent.contains("Edge/") {
+        return engine("EdgeHTML", token_version(agent, "Edge/"));
+    }
+    if let Some(version) = token_version(agent, "Presto/") {
+        return engine("Presto", Some(version));
+    }
+    if agent.contains("Trident/") {
+        return engine("Trident", token_version(agent, "Trident/"));
+    }
+    if agent.contains("MSIE ") && !agent.contains("Opera") {
+        return engine("Trident", None);
+    }
+    if let Some(version) = token_version(agent, "Goanna/") {
+        return engine("Goanna", Some(version));
+    }
+    if agent.contains("AppleWebKit/") {
+        // All browsers on iOS have to use WebKit
+        if !is_ios(agent) {
+            let chrome =
+                token_version(agent, "Chrome/").or_else(|| token_version(agent, "Chromium/"));
+            let major = chrome.and_then(|v| v.split('.').next()?.parse::<u32>().ok());
+            if major.is_some_and(|major| major >= FIRST_BLINK_CHROME) {
+                return engine("Blink", chrome);
+            }
+        }
+        return engine("WebKit", token_version(agent, "AppleWebKit/"));
+    }
+    if agent.contains("KHTML/") || agent.contains("Konqueror/") {
+        return engine("KHTML", token_version(agent, "KHTML/"));
+    }
+    if agent.contains("Gecko/") && !agent.contains("like Gecko") {
+        return engine("Gecko", token_version(agent, "rv:"));
+    }
+    if let Some(version) = token_version(agent, "NetFront/") {
+        return engine("NetFront", Some(version));
+    }
+    Engine::default()
+}
+
+#[test]
+fn test_token_version() {
+    assert_eq!(token_version("Foo/1.2.3 Bar", "Foo/"), Some("1.2.3"));
+    assert_eq!(token_version("Foo/ Bar", "Foo/"), None);
+    assert_eq!(token_version("Bar", "Foo/"), None);
+}
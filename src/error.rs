@@ -0,0 +1,99 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use rmp_serde as rmps;
+
+use crate::ua_core::Category;
+
+/// Error returned when loading a `RegexDatabase` fails.
+#[derive(Debug)]
+pub enum Error {
+    /// The rules could not be read.
+    Io(io::Error),
+    /// The rules are malformed.
+    Decode(rmps::decode::Error),
+    /// The YAML rules are malformed.
+    Yaml(serde_yaml::Error),
+    /// A rule or bot entry lacks a required field or has an invalid value.
+    Invalid(String),
+    /// A rule contains an invalid regular expression.
+    Rule(RuleError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not read regex database: {}", err),
+            Error::Decode(err) => write!(f, "could not decode regex database: {}", err),
+            Error::Yaml(err) => write!(f, "could not parse YAML: {}", err),
+            Error::Invalid(msg) => f.write_str(msg),
+            Error::Rule(err) => err.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) => Some(err),
+            Error::Yaml(err) => Some(err),
+            Error::Invalid(_) => None,
+            Error::Rule(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<rmps::decode::Error> for Error {
+    fn from(err: rmps::decode::Error) -> Error {
+        Error::Decode(err)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Error {
+        Error::Yaml(err)
+    }
+}
+
+impl From<RuleError> for Error {
+    fn from(err: RuleError) -> Error {
+        Error::Rule(err)
+    }
+}
+
+/// A rule whose regular expression could not be compiled.
+#[derive(Debug, Clone)]
+pub struct RuleError {
+    /// The category of the rule.
+    pub category: Category,
+    /// The position of the rule in its category, starting at 0.
+    pub index: usize,
+    /// The pattern of the rule as it was compiled.
+    pub pattern: String,
+    /// The error reported by the `regex` crate.
+    pub error: regex::Error,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "could not compile {} rule {}: {}",
+            self.category, self.index, self.error
+        )
+    }
+}
+
+impl StdError for RuleError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
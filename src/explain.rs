@@ -0,0 +1,126 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_derive::Serialize;
+
+use crate::{Browser, Category, Device, Layer, OS};
+
+/// Explanation how the rules of a `RegexDatabase` parsed a user agent.
+///
+/// For each category it holds the first rule that matched, or `None` if
+/// no rule matched and the result is the default. The `Display`
+/// implementation prints a report suitable for bug reports.
+///
+/// ```rust
+/// use uap_rust::RegexDatabase;
+/// let db = RegexDatabase::builtin();
+/// let explanation = db.explain("Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0");
+/// let browser = explanation.browser.unwrap();
+/// assert_eq!(browser.value.family, "Firefox");
+/// println!("{} {}", browser.index, browser.pattern);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Explanation<'a> {
+    pub user_agent: &'a str,
+    pub browser: Option<RuleMatch<'a, Browser<'a>>>,
+    pub os: Option<RuleMatch<'a, OS<'a>>>,
+    pub device: Option<RuleMatch<'a, Device<'a>>>,
+}
+
+/// A rule matching a user agent and the result it produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RuleMatch<'a, T> {
+    pub category: Category,
+    /// Whether the rule is a custom or a base rule.
+    pub layer: Layer,
+    /// The position of the rule in its category and layer, starting at 0.
+    pub index: usize,
+    /// The regular expression of the rule.
+    pub pattern: &'a str,
+    /// The capture groups, starting with the whole match at 0.
+    pub captures: Vec<Option<&'a str>>,
+    /// The replacement templates of the rule by field name, e.g.
+    /// `("family_replacement", "$1 Mobile")`.
+    pub templates: Vec<(&'static str, &'a str)>,
+    /// The result after applying the templates.
+    pub value: T,
+}
+
+impl<'a> fmt::Display for Explanation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "user agent: {}", self.user_agent)?;
+        write_match(f, Category::Browser, self.browser.as_ref())?;
+        write_match(f, Category::OS, self.os.as_ref())?;
+        write_match(f, Category::Device, self.device.as_ref())
+    }
+}
+
+fn write_match<T: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    category: Category,
+    rule: Option<&RuleMatch<'_, T>>,
+) -> fmt::Result {
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return writeln!(f, "{}: no rule matched", category),
+    };
+    writeln!(
+        f,
+        "{}: {} rule {} {:?}",
+        category, rule.layer, rule.index, rule.pattern
+    )?;
+    write!(f, "  captures:")?;
+    for (i, capture) in rule.captures.iter().enumerate().skip(1) {
+        match capture {
+            Some(capture) => write!(f, " ${}={:?}", i, capture)?,
+            None => write!(f, " ${}=-", i)?,
+        }
+    }
+    writeln!(f)?;
+    if !rule.templates.is_empty() {
+        write!(f, "  templates:")?;
+        for (field, template) in &rule.templates {
+            write!(f, " {}={:?}", field, template)?;
+        }
+        writeln!(f)?;
+    }
+    writeln!(f, "  result: {:?}", rule.value)
+}
+
+#[test]
+fn test_explain() {
+    use crate::RegexDatabase;
+
+    let yaml = r#"
+user_agent_parsers:
+  - regex: 'Never/(\d+)'
+  - regex: '(Foo)Bar/(\d+)(?:\.(\d+))?'
+    family_replacement: '$1 App'
+os_parsers: []
+device_parsers:
+  - regex: '; (FooPhone) (\d+)'
+    model_replacement: '$1 $2'
+"#;
+    let db = RegexDatabase::from_yaml(yaml).unwrap();
+    let explanation = db.explain("Mozilla/5.0 (FooOS; FooPhone 3) FooBar/7");
+
+    let browser = explanation.browser.as_ref().unwrap();
+    assert_eq!(browser.index, 1);
+    assert_eq!(browser.pattern, r"(Foo)Bar/(\d+)(?:\.(\d+))?");
+    assert_eq!(
+        browser.captures,
+        vec![Some("FooBar/7"), Some("Foo"), Some("7"), None]
+    );
+    assert_eq!(browser.templates, vec![("family_replacement", "$1 App")]);
+    assert_eq!(browser.value.family, "Foo App");
+    assert_eq!(explanation.os, None);
+    let device = explanation.device.as_ref().unwrap();
+    assert_eq!(device.value.model.as_ref().unwrap(), "FooPhone 3");
+
+    let report = explanation.to_string();
+    assert!(report.contains("browser: base rule 1 "), "{}", report);
+    assert!(report.contains("$1=\"Foo\" $2=\"7\" $3=-"), "{}", report);
+    assert!(report.contains("os: no rule matched"), "{}", report);
+}
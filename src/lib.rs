@@ -19,14 +19,14 @@
 //! let browser = parser.browser();
 //! assert_eq!(browser.family, "Mobile Safari");
 //! let browser_version = browser.version().unwrap();
-//! assert_eq!(browser_version.major, 5);
-//! assert_eq!(browser_version.minor, 1);
+//! assert_eq!(browser_version.major(), 5);
+//! assert_eq!(browser_version.minor(), 1);
 //!
 //! let os = parser.os();
 //! assert_eq!(os.family, "iOS");
 //! let os_version = os.version().unwrap();
-//! assert_eq!(os_version.major, 5);
-//! assert_eq!(os_version.minor, 1);
+//! assert_eq!(os_version.major(), 5);
+//! assert_eq!(os_version.minor(), 1);
 //!
 //! let device = parser.device();
 //! assert_eq!(device.family, "iPhone");
@@ -48,22 +48,124 @@
 //! The `OwningParser` variant is a convenience wrapper around
 //! `BorrowingParser` to allow storing the user agent along the parser, which
 //! is not trivial, since rust does not understand self-referential structs.
-#[macro_use]
-extern crate rental;
-
-use semver_parser::version::{parse as parse_version, Version};
+//! Besides `String`, `Arc<str>` and `Rc<str>` it can own any user agent
+//! with a stable address, e.g. a `Box<str>` or a `Vec<u8>` with
+//! `OwningParser::from_owner`. With the `bytes` feature a `bytes::Bytes`
+//! can be used by wrapping it in `StableBytes`.
+//! To keep a single result independent of the user agent, convert it with
+//! `into_owned()` or `to_static()`, e.g. `parser.browser().to_static()`.
+//!
+//! ## Custom rules
+//!
+//! The parsers use the rules from [uap-core](https://github.com/ua-parser/uap-core)
+//! bundled with the crate. A different rule set, either uap-core's `regexes.yaml`
+//! or the MessagePack format generated by `resources.py`, can be loaded into a
+//! `RegexDatabase`:
+//!
+//! ```rust,no_run
+//! use uap_rust::unsync::BorrowingParser as Parser;
+//! use uap_rust::RegexDatabase;
+//! let db = RegexDatabase::from_yaml_file("regexes.yaml").unwrap();
+//! let parser = Parser::with_database("Mozilla/5.0 ...", &db);
+//! println!("{}", parser.browser().family);
+//! ```
+//!
+//! To keep the built-in rules and only add a few, a `DatabaseBuilder` layers
+//! custom rules before or after them and can disable single built-in rules.
+//!
+//! Loading fails with `Error::Rule` if a regular expression is invalid.
+//! With `LoadOptions::lenient` such rules are skipped and reported as
+//! diagnostics instead. The built-in rules are loaded on first use; call
+//! `try_init()` on startup to handle a failure there as an error.
+//!
+//! Each rule is compiled when it is first tried, so a service that only
+//! parses the operating system never compiles the device rules. Call
+//! `warm_up()`, or `RegexDatabase::warm_up` for other databases, to
+//! compile everything on startup instead.
+//!
+//! Long-running services can keep the rules in a `ReloadableDatabase`,
+//! which swaps in new rules from a file, on demand or when the file changes,
+//! without blocking readers.
+//!
+//! ## Batches
+//!
+//! `parse_all` parses a batch of user agents, e.g. the lines of a log file.
+//! Identical user agents are parsed only once and, with the `rayon` feature,
+//! distinct ones in parallel. The results are in the order of the input:
+//!
+//! ```rust
+//! let agents = ["Firefox/60.0", "curl/7.64.1", "Firefox/60.0"];
+//! for parser in &uap_rust::parse_all(&agents) {
+//!     println!("{}", parser.browser().family);
+//! }
+//! ```
+//!
+//! ## Caching
+//!
+//! Most traffic comes from a small number of distinct user agents. A
+//! `sync::ParserCache` keeps the parsers of recently seen user agents, so
+//! their results are shared instead of parsed again:
+//!
+//! ```rust
+//! use uap_rust::sync::ParserCache;
+//! use uap_rust::UserAgentInformation;
+//! let cache = ParserCache::new(10_000).max_bytes(16 << 20);
+//! let parser = cache.get("Mozilla/5.0 ...");
+//! assert!(!parser.is_bot());
+//! println!("hit ratio: {}", cache.stats().hit_ratio());
+//! ```
+//!
+//! ## Startup time
+//!
+//! The built-in rules are compiled on first use. With the `prebuilt` feature
+//! they are compiled to DFAs at build time instead, which makes the first
+//! parse considerably faster at the cost of a larger binary.
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::str::FromStr;
 
+mod automation;
+mod batch;
+mod bot;
+mod bot_info;
+mod cache;
+pub mod client_hints;
+mod device_type;
+mod engine;
+mod error;
+mod explain;
+mod matcher;
 mod parser;
+pub mod policy;
+mod prefilter;
+mod reload;
 mod ua_core;
+mod verify;
+mod version;
+mod webview;
 
+pub use crate::automation::{Automation, AutomationTool};
+pub use crate::batch::{parse_all, Batch};
+pub use crate::bot_info::{BotCategory, BotEntry, BotInfo, BotTable};
+pub use crate::client_hints::ClientHints;
+pub use crate::device_type::DeviceType;
+pub use crate::error::{Error, RuleError};
+pub use crate::explain::{Explanation, RuleMatch};
 pub use crate::parser::sync;
 pub use crate::parser::unsync;
+#[cfg(feature = "bytes")]
+pub use crate::parser::StableBytes;
 pub use crate::parser::UserAgentInformation;
+pub use crate::reload::{FileWatch, ReloadEvent, ReloadableDatabase, RuleCounts};
+pub use crate::ua_core::{Category, DatabaseBuilder, Layer, LoadOptions, RegexDatabase};
+pub use crate::verify::{BotVerifier, Resolver, Verification};
+pub use crate::version::{ParseVersionError, UaVersion};
+pub use stable_deref_trait::StableDeref;
 
 /// `Browser` contains browser information from the user agent.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Browser<'a> {
     pub family: Cow<'a, str>,
     pub major: Option<Cow<'a, str>>,
@@ -72,7 +174,8 @@ pub struct Browser<'a> {
 }
 
 /// `OS` contains the operating system information from the user agent.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OS<'a> {
     pub family: Cow<'a, str>,
     pub major: Option<Cow<'a, str>>,
@@ -82,15 +185,72 @@ pub struct OS<'a> {
 }
 
 /// `Device` contains the device information from the user agent.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Device<'a> {
     pub family: Cow<'a, str>,
     pub brand: Option<Cow<'a, str>>,
     pub model: Option<Cow<'a, str>>,
 }
 
+/// `Engine` contains the rendering engine of the browser, e.g. `Blink` or
+/// `Gecko`, derived from the user agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Engine<'a> {
+    pub family: Cow<'a, str>,
+    pub major: Option<Cow<'a, str>>,
+    pub minor: Option<Cow<'a, str>>,
+    pub patch: Option<Cow<'a, str>>,
+}
+
+/// `WebView` describes the in-app browser or WebView of a native app, e.g.
+/// the browser built into Facebook or an Android WebView.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WebView<'a> {
+    /// The app hosting the WebView, e.g. `Instagram`, if it identifies
+    /// itself.
+    pub app: Option<Cow<'a, str>>,
+    /// The version of the app.
+    pub version: Option<Cow<'a, str>>,
+}
+
+/// `UserAgentSummary` combines all information parsed from a user agent.
+///
+/// With the `serde` feature it serializes to an object with the fields
+/// `user_agent`, `browser`, `os` and `device`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UserAgentSummary<'a> {
+    pub user_agent: Cow<'a, str>,
+    pub browser: Browser<'a>,
+    pub os: OS<'a>,
+    pub device: Device<'a>,
+}
+
 static DEFAULT_NAME: &str = "Other";
 
+/// Loads the built-in rules now instead of on first use.
+///
+/// The parsers panic if the built-in rules cannot be loaded. Calling
+/// `try_init()` on startup reports this as an error instead. The rules are
+/// still compiled on first use, see `warm_up`.
+pub fn try_init() -> Result<(), Error> {
+    RegexDatabase::load().map(|_| ())
+}
+
+/// Compiles all built-in rules now instead of on first use, see
+/// `RegexDatabase::warm_up`.
+///
+/// Fails with the first rule which cannot be compiled.
+pub fn warm_up() -> Result<(), Error> {
+    match RegexDatabase::load()?.warm_up().into_iter().next() {
+        Some(err) => Err(Error::Rule(err)),
+        None => Ok(()),
+    }
+}
+
 impl<'a> Default for Browser<'a> {
     fn default() -> Browser<'a> {
         Browser {
@@ -124,6 +284,17 @@ impl<'a> Default for OS<'a> {
     }
 }
 
+impl<'a> Default for Engine<'a> {
+    fn default() -> Engine<'a> {
+        Engine {
+            family: DEFAULT_NAME.into(),
+            major: None,
+            minor: None,
+            patch: None,
+        }
+    }
+}
+
 macro_rules! default_parse {
     ($obj:ident, $name:ident, $default:ident) => {
         $obj.$name
@@ -133,20 +304,38 @@ macro_rules! default_parse {
     };
 }
 
+fn owned(value: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(value.into_owned())
+}
+
+fn owned_opt(value: Option<Cow<'_, str>>) -> Option<Cow<'static, str>> {
+    value.map(owned)
+}
+
 impl<'a> Browser<'a> {
-    pub fn version(&self) -> Option<Version> {
-        match (&self.major, &self.minor, &self.patch) {
-            (Some(major), Some(minor), Some(patch)) => {
-                parse_version(&format!("{}.{}.{}", major, minor, patch)).ok()
-            }
-            (Some(major), Some(minor), None) => {
-                parse_version(&format!("{}.{}.0", major, minor)).ok()
-            }
-            (Some(major), None, None) => parse_version(&format!("{}.0.0", major)).ok(),
-            _ => parse_version("").ok(),
+    /// Converts the browser into one which does not borrow the user agent.
+    pub fn into_owned(self) -> Browser<'static> {
+        Browser {
+            family: owned(self.family),
+            major: owned_opt(self.major),
+            minor: owned_opt(self.minor),
+            patch: owned_opt(self.patch),
         }
     }
 
+    /// Returns a copy of the browser which does not borrow the user agent.
+    pub fn to_static(&self) -> Browser<'static> {
+        self.clone().into_owned()
+    }
+
+    pub fn version(&self) -> Option<UaVersion> {
+        UaVersion::from_fields([
+            self.major.as_deref(),
+            self.minor.as_deref(),
+            self.patch.as_deref(),
+        ])
+    }
+
     pub fn major_or<T: FromStr>(&self, default: T) -> T {
         default_parse!(self, major, default)
     }
@@ -161,22 +350,33 @@ impl<'a> Browser<'a> {
 }
 
 impl<'a> OS<'a> {
-    pub fn version(&self) -> Option<Version> {
-        match (&self.major, &self.minor, &self.patch, &self.patch_minor) {
-            (Some(major), Some(minor), Some(patch), Some(patch_minor)) => {
-                parse_version(&format!("{}.{}.{}-{}", major, minor, patch, patch_minor)).ok()
-            }
-            (Some(major), Some(minor), Some(patch), None) => {
-                parse_version(&format!("{}.{}.{}", major, minor, patch)).ok()
-            }
-            (Some(major), Some(minor), None, None) => {
-                parse_version(&format!("{}.{}.0", major, minor)).ok()
-            }
-            (Some(major), None, None, None) => parse_version(&format!("{}", major)).ok(),
-            _ => parse_version("").ok(),
+    /// Converts the operating system into one which does not borrow the user
+    /// agent.
+    pub fn into_owned(self) -> OS<'static> {
+        OS {
+            family: owned(self.family),
+            major: owned_opt(self.major),
+            minor: owned_opt(self.minor),
+            patch: owned_opt(self.patch),
+            patch_minor: owned_opt(self.patch_minor),
         }
     }
 
+    /// Returns a copy of the operating system which does not borrow the user
+    /// agent.
+    pub fn to_static(&self) -> OS<'static> {
+        self.clone().into_owned()
+    }
+
+    pub fn version(&self) -> Option<UaVersion> {
+        UaVersion::from_fields([
+            self.major.as_deref(),
+            self.minor.as_deref(),
+            self.patch.as_deref(),
+            self.patch_minor.as_deref(),
+        ])
+    }
+
     pub fn major_or<T: FromStr>(&self, default: T) -> T {
         default_parse!(self, major, default)
     }
@@ -193,3 +393,122 @@ impl<'a> OS<'a> {
         default_parse!(self, patch_minor, default)
     }
 }
+
+impl<'a> Device<'a> {
+    /// Converts the device into one which does not borrow the user agent.
+    pub fn into_owned(self) -> Device<'static> {
+        Device {
+            family: owned(self.family),
+            brand: owned_opt(self.brand),
+            model: owned_opt(self.model),
+        }
+    }
+
+    /// Returns a copy of the device which does not borrow the user agent.
+    pub fn to_static(&self) -> Device<'static> {
+        self.clone().into_owned()
+    }
+}
+
+impl<'a> Engine<'a> {
+    pub fn version(&self) -> Option<UaVersion> {
+        UaVersion::from_fields([
+            self.major.as_deref(),
+            self.minor.as_deref(),
+            self.patch.as_deref(),
+        ])
+    }
+
+    pub fn major_or<T: FromStr>(&self, default: T) -> T {
+        default_parse!(self, major, default)
+    }
+
+    pub fn minor_or<T: FromStr>(&self, default: T) -> T {
+        default_parse!(self, minor, default)
+    }
+
+    pub fn patch_or<T: FromStr>(&self, default: T) -> T {
+        default_parse!(self, patch, default)
+    }
+
+    /// Converts the engine into one which does not borrow the user agent.
+    pub fn into_owned(self) -> Engine<'static> {
+        Engine {
+            family: owned(self.family),
+            major: owned_opt(self.major),
+            minor: owned_opt(self.minor),
+            patch: owned_opt(self.patch),
+        }
+    }
+
+    /// Returns a copy of the engine which does not borrow the user agent.
+    pub fn to_static(&self) -> Engine<'static> {
+        self.clone().into_owned()
+    }
+}
+
+impl<'a> WebView<'a> {
+    /// Converts the WebView into one which does not borrow the user agent.
+    pub fn into_owned(self) -> WebView<'static> {
+        WebView {
+            app: owned_opt(self.app),
+            version: owned_opt(self.version),
+        }
+    }
+
+    /// Returns a copy of the WebView which does not borrow the user agent.
+    pub fn to_static(&self) -> WebView<'static> {
+        self.clone().into_owned()
+    }
+}
+
+impl<'a> UserAgentSummary<'a> {
+    /// Converts the summary into one which does not borrow the user agent.
+    pub fn into_owned(self) -> UserAgentSummary<'static> {
+        UserAgentSummary {
+            user_agent: owned(self.user_agent),
+            browser: self.browser.into_owned(),
+            os: self.os.into_owned(),
+            device: self.device.into_owned(),
+        }
+    }
+}
+
+#[test]
+fn test_into_owned() {
+    use crate::unsync::BorrowingParser;
+    let (browser, os, device) = {
+        let agent = String::from("Mozilla/5.0 (iPhone; CPU iPhone OS 5_1_1 like Mac OS X) AppleWebKit/534.46 (KHTML, like Gecko) Version/5.1 Mobile/9B206 Safari/7534.48.3");
+        let parser = BorrowingParser::new(&agent);
+        (
+            parser.browser().to_static(),
+            parser.os().to_static(),
+            parser.device().clone().into_owned(),
+        )
+    };
+    assert_eq!(browser.family, "Mobile Safari");
+    assert_eq!(browser.minor_or(0), 1);
+    assert_eq!(os.family, "iOS");
+    assert_eq!(os.patch_or(0), 1);
+    assert_eq!(device.brand.as_deref(), Some("Apple"));
+    let browser = std::thread::spawn(move || browser.family).join().unwrap();
+    assert_eq!(browser, "Mobile Safari");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    use crate::unsync::BorrowingParser;
+    let parser = BorrowingParser::new(
+        "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0",
+    );
+    let summary = parser.summary();
+    let json = serde_json::to_value(&summary).unwrap();
+    assert_eq!(json["browser"]["family"], "Firefox");
+    assert_eq!(json["browser"]["major"], "60");
+    assert_eq!(json["os"]["family"], "Linux");
+    assert_eq!(json["os"]["patch_minor"], serde_json::Value::Null);
+    assert_eq!(json["device"]["family"], "Other");
+    let decoded: UserAgentSummary<'_> = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, summary);
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use once_cell::sync::OnceCell;
+use regex::{Captures, Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use regex_syntax::Parser;
+
+#[cfg(feature = "prebuilt")]
+use regex_automata::{
+    dfa::{sparse, Automaton},
+    Input,
+};
+
+/// DFA compiled by `build.rs` for a rule of the built-in database.
+#[cfg(feature = "prebuilt")]
+pub(crate) type Dfa = sparse::DFA<&'static [u8]>;
+
+/// The regular expression of a rule.
+///
+/// The pattern is only parsed when the rule is loaded, the `Regex` is
+/// compiled when the rule is first tried, see `RegexDatabase::warm_up`.
+/// Only patterns which might exceed the size limit are compiled when they
+/// are loaded.
+///
+/// Rules of the built-in database come with a prebuilt DFA if the
+/// `prebuilt` feature is enabled. The DFA decides whether the rule
+/// matches, the `Regex` is only compiled once a match needs its capture
+/// groups.
+pub(crate) struct Matcher {
+    pattern: String,
+    /// The compiled regex. Compiling could only fail if `estimate_size`
+    /// underestimated the pattern, then the rule never matches.
+    regex: OnceCell<Result<Regex, regex::Error>>,
+    #[cfg(feature = "prebuilt")]
+    dfa: Option<Dfa>,
+}
+
+impl Matcher {
+    /// Checks the syntax of `pattern`, on failure returns it with the
+    /// error. Patterns which might exceed the size limit are compiled
+    /// right away, so that they fail here instead of on first use.
+    pub(crate) fn new(pattern: String) -> Result<Matcher, (String, regex::Error)> {
+        let hir = match Parser::new().parse(&pattern) {
+            Ok(hir) => hir,
+            Err(err) => return Err((pattern, regex::Error::Syntax(err.to_string()))),
+        };
+        let matcher = Matcher::lazy(pattern);
+        if estimate_size(&hir) > SIZE_LIMIT / 2 {
+            if let Err(err) = matcher.compile() {
+                return Err((matcher.pattern.clone(), err.clone()));
+            }
+        }
+        Ok(matcher)
+    }
+
+    /// Creates a matcher for `pattern`, which must compile, using `dfa`
+    /// to find matches.
+    #[cfg(feature = "prebuilt")]
+    pub(crate) fn prebuilt(pattern: String, dfa: Dfa) -> Matcher {
+        Matcher {
+            dfa: Some(dfa),
+            ..Matcher::lazy(pattern)
+        }
+    }
+
+    fn lazy(pattern: String) -> Matcher {
+        Matcher {
+            pattern,
+            regex: OnceCell::INIT,
+            #[cfg(feature = "prebuilt")]
+            dfa: None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Compiles the regex if that did not happen yet.
+    pub(crate) fn compile(&self) -> Result<&Regex, &regex::Error> {
+        self.regex.get_or_init(|| compile(&self.pattern)).as_ref()
+    }
+
+    pub(crate) fn captures<'a>(&self, agent: &'a str) -> Option<Captures<'a>> {
+        #[cfg(feature = "prebuilt")]
+        {
+            if let Some(dfa) = &self.dfa {
+                // On errors, e.g. Unicode word boundaries, fall back to the regex
+                if let Ok(None) = dfa.try_search_fwd(&Input::new(agent).earliest(true)) {
+                    return None;
+                }
+            }
+        }
+        self.compile().ok()?.captures(agent)
+    }
+}
+
+const SIZE_LIMIT: usize = 2 * 10_485_760;
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).size_limit(SIZE_LIMIT).build()
+}
+
+/// Estimates the size of the compiled regex from the number of NFA states,
+/// which Unicode classes multiply by the UTF-8 sequences of their ranges
+/// and counted repetitions by the count. For `\w` the estimate is about
+/// twice the actual size.
+fn estimate_size(hir: &Hir) -> usize {
+    /// Bytes per NFA state, including the transitions.
+    const STATE: usize = 32;
+
+    fn states(hir: &Hir) -> usize {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => 1,
+            HirKind::Literal(literal) => literal.0.len(),
+            HirKind::Class(Class::Unicode(class)) => 4 * class.ranges().len() + 1,
+            HirKind::Class(Class::Bytes(class)) => class.ranges().len() + 1,
+            HirKind::Repetition(rep) => {
+                let count = rep.max.unwrap_or(rep.min).max(1) as usize;
+                states(&rep.sub).saturating_mul(count).saturating_add(1)
+            }
+            HirKind::Capture(capture) => states(&capture.sub).saturating_add(2),
+            HirKind::Concat(subs) | HirKind::Alternation(subs) => subs
+                .iter()
+                .fold(subs.len(), |sum, sub| sum.saturating_add(states(sub))),
+        }
+    }
+    states(hir).saturating_mul(STATE)
+}
+
+impl Clone for Matcher {
+    fn clone(&self) -> Self {
+        let matcher = Matcher {
+            pattern: self.pattern.clone(),
+            regex: OnceCell::INIT,
+            #[cfg(feature = "prebuilt")]
+            dfa: self.dfa.clone(),
+        };
+        if let Some(regex) = self.regex.get() {
+            let _ = matcher.regex.set(regex.clone());
+        }
+        matcher
+    }
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Matcher").field(&self.pattern).finish()
+    }
+}
+
+/// The DFAs of the built-in rules by category, in the order browser,
+/// operating system and device. `None` for rules without a DFA.
+#[cfg(feature = "prebuilt")]
+pub(crate) fn prebuilt() -> Option<[Vec<Option<Dfa>>; 3]> {
+    static AUTOMATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/automata.bin"));
+
+    let mut data = AUTOMATA.strip_prefix(b"UAPD")?;
+    let read_u32 = |data: &mut &'static [u8]| {
+        let (n, rest) = data.split_first_chunk::<4>()?;
+        *data = rest;
+        Some(u32::from_le_bytes(*n) as usize)
+    };
+    let mut category = || {
+        let count = read_u32(&mut data)?;
+        let mut dfas = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = read_u32(&mut data)?;
+            let bytes = data.get(..len)?;
+            data = &data[len..];
+            dfas.push(if len == 0 {
+                None
+            } else {
+                Some(Dfa::from_bytes(bytes).ok()?.0)
+            });
+        }
+        Some(dfas)
+    };
+    Some([category()?, category()?, category()?])
+}
+
+#[test]
+fn test_size_limit() {
+    let err = Matcher::new(r"(Bar)/\w{100000}".into()).unwrap_err();
+    assert!(
+        matches!(err.1, regex::Error::CompiledTooBig(_)),
+        "{:?}",
+        err
+    );
+    // Large, but below the limit
+    let matcher = Matcher::new(r"(Bar)/\w{300}".into()).unwrap();
+    assert!(matcher.regex.get().is_some());
+    // Small patterns are still compiled on first use
+    let matcher = Matcher::new(r"(Foo)/(\d+)".into()).unwrap();
+    assert!(matcher.regex.get().is_none());
+}
@@ -1,53 +1,200 @@
-use crate::{Browser, Device, OS};
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::str::{self, Utf8Error};
+use std::sync::Arc;
+
+use stable_deref_trait::StableDeref;
+
+use crate::automation::detect as detect_automation;
+use crate::bot_info::builtin_lookup;
+use crate::device_type::classify;
+use crate::{
+    Automation, BotInfo, Browser, Device, DeviceType, Engine, RegexDatabase, UserAgentSummary,
+    WebView, OS,
+};
+
+/// The user agent and database of an `OwningParser`.
+///
+/// The results of an `OwningParser` borrow from its user agent and database.
+/// Both are behind pointers with a stable address, so the borrows stay valid
+/// when the parser is moved. `Owner` hands them out with an unbounded
+/// lifetime, the parser has to make sure that nothing borrowing from them
+/// outlives the `Owner`.
+struct Owner<S> {
+    user_agent: &'static str,
+    db: &'static RegexDatabase,
+    _db: Arc<RegexDatabase>,
+    // Moving a `Box` asserts unique access to its contents, which would
+    // invalidate `user_agent`. `MaybeUninit` opts out of this.
+    owner: MaybeUninit<S>,
+}
 
-rental! {
-    mod owned {
-        use std::ops::Deref;
-        use stable_deref_trait::StableDeref;
-        use crate::{Browser as BrowserBorrowed, Device as DeviceBorrowed, OS as OSBorrowed};
+impl<S> Owner<S>
+where
+    S: StableDeref,
+    S::Target: AsRef<[u8]>,
+{
+    fn new(owner: S, db: Arc<RegexDatabase>) -> Result<Self, Utf8Error> {
+        // `as_ref` is called only once, another call might return other
+        // bytes which were not validated.
+        let user_agent: *const str = str::from_utf8((*owner).as_ref())?;
+        let owner = MaybeUninit::new(owner);
+        // SAFETY: the target of a `StableDeref` does not move when `owner`
+        // is moved, so the bytes borrowed from it stay in place. `owner` is
+        // dropped only when `Owner` is dropped.
+        let user_agent = unsafe { &*user_agent };
+        // SAFETY: the database is kept alive by `_db`.
+        let db_ref = unsafe { &*Arc::as_ptr(&db) };
+        Ok(Owner {
+            user_agent,
+            db: db_ref,
+            _db: db,
+            owner,
+        })
+    }
+}
 
-        #[rental(covariant)]
-        pub struct Browser<T>
-        where
-            T: Deref<Target=str> + StableDeref,
-        {
-            ua: T,
-            browser: BrowserBorrowed<'ua>,
-        }
+impl<S> Owner<S> {
+    fn user_agent(&self) -> &'static str {
+        self.user_agent
+    }
 
-        #[rental(covariant)]
-        pub struct Device<T>
-        where
-            T: Deref<Target=str> + StableDeref,
-        {
-            ua: T,
-            device: DeviceBorrowed<'ua>,
-        }
+    fn db(&self) -> &'static RegexDatabase {
+        self.db
+    }
+}
 
-        #[rental(covariant)]
-        pub struct OS<T>
-        where
-            T: Deref<Target=str> + StableDeref,
-        {
-            ua: T,
-            os: OSBorrowed<'ua>,
-        }
+impl<S> Drop for Owner<S> {
+    fn drop(&mut self) {
+        // SAFETY: `owner` is always initialized and not used afterwards.
+        unsafe { ptr::drop_in_place(self.owner.as_mut_ptr()) }
     }
 }
 
+/// Wraps `bytes::Bytes` to use it as the user agent of an `OwningParser`.
+///
+/// `Bytes` does not implement `StableDeref` itself.
+#[cfg(feature = "bytes")]
+#[derive(Debug, Clone)]
+pub struct StableBytes(pub bytes::Bytes);
+
+#[cfg(feature = "bytes")]
+impl std::ops::Deref for StableBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// SAFETY: the contents of `Bytes` are on the heap or static and do not move
+// with the `Bytes`.
+#[cfg(feature = "bytes")]
+unsafe impl StableDeref for StableBytes {}
+
 pub trait UserAgentInformation {
     fn user_agent(&self) -> &str;
-    fn browser(&self) -> &Browser;
-    fn device(&self) -> &Device;
-    fn os(&self) -> &OS;
+    fn browser(&self) -> &Browser<'_>;
+    fn device(&self) -> &Device<'_>;
+    fn os(&self) -> &OS<'_>;
+    fn engine(&self) -> &Engine<'_>;
     fn is_bot(&self) -> bool;
+
+    /// Returns the in-app browser or WebView the user agent belongs to,
+    /// `None` for standalone browsers.
+    fn webview(&self) -> Option<&WebView<'_>>;
+
+    fn is_webview(&self) -> bool {
+        self.webview().is_some()
+    }
+
+    /// Returns the form factor of the device.
+    fn device_type(&self) -> DeviceType {
+        classify(self)
+    }
+
+    /// Returns `true` for phones, but not for tablets.
+    fn is_mobile(&self) -> bool {
+        self.device_type() == DeviceType::Phone
+    }
+
+    fn is_tablet(&self) -> bool {
+        self.device_type() == DeviceType::Tablet
+    }
+
+    fn is_desktop(&self) -> bool {
+        self.device_type() == DeviceType::Desktop
+    }
+
+    /// Returns name, operator and category of the bot using the built-in
+    /// `BotTable`, `None` for other user agents.
+    ///
+    /// The built-in table cannot be changed. To use entries added at
+    /// runtime, look the parser up in your own table with
+    /// `BotTable::lookup`.
+    fn bot_info(&self) -> Option<BotInfo<'_>> {
+        builtin_lookup(self)
+    }
+
+    /// Returns the headless browser or automation framework named in the
+    /// user agent, see also `ClientHints::automation`.
+    fn automation(&self) -> Option<Automation<'_>> {
+        detect_automation(self.user_agent())
+    }
+
+    /// Returns the browser, device and operating system together.
+    fn summary(&self) -> UserAgentSummary<'_> {
+        UserAgentSummary {
+            user_agent: self.user_agent().into(),
+            browser: self.browser().clone(),
+            os: self.os().clone(),
+            device: self.device().clone(),
+        }
+    }
+}
+
+impl<T: UserAgentInformation + ?Sized> UserAgentInformation for Arc<T> {
+    fn user_agent(&self) -> &str {
+        (**self).user_agent()
+    }
+
+    fn browser(&self) -> &Browser<'_> {
+        (**self).browser()
+    }
+
+    fn device(&self) -> &Device<'_> {
+        (**self).device()
+    }
+
+    fn os(&self) -> &OS<'_> {
+        (**self).os()
+    }
+
+    fn engine(&self) -> &Engine<'_> {
+        (**self).engine()
+    }
+
+    fn is_bot(&self) -> bool {
+        (**self).is_bot()
+    }
+
+    fn webview(&self) -> Option<&WebView<'_>> {
+        (**self).webview()
+    }
 }
 
 macro_rules! create_parser {
     ($rc:path, $cell:path) => {
-        use super::owned;
+        use super::Owner;
         use super::UserAgentInformation;
-        use crate::{Browser, Device, OS};
+        use crate::bot::Verdict;
+        use crate::engine::parse_engine;
+        use crate::ua_core::UA_PARSER_REGEXES;
+        use crate::webview::parse_webview;
+        use crate::{Browser, Device, Engine, Explanation, RegexDatabase, WebView, OS};
+        use stable_deref_trait::StableDeref;
+        use std::str::Utf8Error;
+        use std::sync::Arc;
         use $cell;
         use $rc as RefCount;
 
@@ -61,18 +208,30 @@ macro_rules! create_parser {
         /// also allows stroing a `Arc<str>` or `Rc<str>` respectively.
         pub struct BorrowingParser<'a> {
             user_agent: &'a str,
+            db: &'a RegexDatabase,
             browser: OnceCell<Browser<'a>>,
             device: OnceCell<Device<'a>>,
             os: OnceCell<OS<'a>>,
+            engine: OnceCell<Engine<'a>>,
+            webview: OnceCell<Option<WebView<'a>>>,
         }
 
         impl<'a> BorrowingParser<'a> {
             pub fn new(user_agent: &'a str) -> Self {
+                Self::with_database(user_agent, &UA_PARSER_REGEXES)
+            }
+
+            /// Creates a parser using the rules of `db` instead of the
+            /// built-in database.
+            pub fn with_database(user_agent: &'a str, db: &'a RegexDatabase) -> Self {
                 Self {
                     user_agent,
+                    db,
                     browser: OnceCell::INIT,
                     device: OnceCell::INIT,
                     os: OnceCell::INIT,
+                    engine: OnceCell::INIT,
+                    webview: OnceCell::INIT,
                 }
             }
 
@@ -80,20 +239,55 @@ macro_rules! create_parser {
                 self.user_agent
             }
 
-            pub fn browser(&self) -> &Browser {
-                self.browser.get_or_init(|| self.user_agent.into())
+            pub fn browser(&self) -> &Browser<'_> {
+                self.browser
+                    .get_or_init(|| self.db.browser(self.user_agent))
+            }
+
+            pub fn device(&self) -> &Device<'_> {
+                self.device.get_or_init(|| self.db.device(self.user_agent))
+            }
+
+            pub fn os(&self) -> &OS<'_> {
+                self.os.get_or_init(|| self.db.os(self.user_agent))
             }
 
-            pub fn device(&self) -> &Device {
-                self.device.get_or_init(|| self.user_agent.into())
+            pub fn engine(&self) -> &Engine<'_> {
+                self.engine.get_or_init(|| parse_engine(self.user_agent))
             }
 
-            pub fn os(&self) -> &OS {
-                self.os.get_or_init(|| self.user_agent.into())
+            /// Returns the in-app browser or WebView, `None` for standalone
+            /// browsers.
+            pub fn webview(&self) -> Option<&WebView<'_>> {
+                self.webview
+                    .get_or_init(|| parse_webview(self.user_agent))
+                    .as_ref()
             }
 
+            pub fn is_webview(&self) -> bool {
+                self.webview().is_some()
+            }
+
+            /// Reports which rules determined the results, see `Explanation`.
+            pub fn explain(&self) -> Explanation<'a> {
+                self.db.explain(self.user_agent)
+            }
+
+            /// Returns `true` if the device is "Spider". Unless `device`
+            /// was already parsed, this only tries the Spider rules, see
+            /// `RegexDatabase::is_bot`.
             pub fn is_bot(&self) -> bool {
-                self.device().family == "Spider"
+                if let Some(device) = self.device.get() {
+                    return device.family == "Spider";
+                }
+                match self.db.check_bot(self.user_agent) {
+                    Verdict::Human => false,
+                    Verdict::Bot(device) => {
+                        let _ = self.device.set(device);
+                        true
+                    }
+                    Verdict::Unsure => self.device().family == "Spider",
+                }
             }
         }
 
@@ -102,21 +296,29 @@ macro_rules! create_parser {
                 BorrowingParser::user_agent(self)
             }
 
-            fn browser(&self) -> &Browser {
+            fn browser(&self) -> &Browser<'_> {
                 BorrowingParser::browser(self)
             }
 
-            fn device(&self) -> &Device {
+            fn device(&self) -> &Device<'_> {
                 BorrowingParser::device(self)
             }
 
-            fn os(&self) -> &OS {
+            fn os(&self) -> &OS<'_> {
                 BorrowingParser::os(self)
             }
 
+            fn engine(&self) -> &Engine<'_> {
+                BorrowingParser::engine(self)
+            }
+
             fn is_bot(&self) -> bool {
                 BorrowingParser::is_bot(self)
             }
+
+            fn webview(&self) -> Option<&WebView<'_>> {
+                BorrowingParser::webview(self)
+            }
         }
 
         /// Parser for a user agent. The parser takes
@@ -127,70 +329,155 @@ macro_rules! create_parser {
         ///
         /// If you hold a reference to user agent string for the lifetime
         /// of the parser, `BorrowingParser` might be better suited.
-        pub struct OwningParser {
-            ua: RefCount<str>,
-            browser: OnceCell<owned::Browser<RefCount<str>>>,
-            device: OnceCell<owned::Device<RefCount<str>>>,
-            os: OnceCell<owned::OS<RefCount<str>>>,
+        pub struct OwningParser<S = RefCount<str>> {
+            browser: OnceCell<Browser<'static>>,
+            device: OnceCell<Device<'static>>,
+            os: OnceCell<OS<'static>>,
+            engine: OnceCell<Engine<'static>>,
+            webview: OnceCell<Option<WebView<'static>>>,
+            // Declared last to drop the results borrowing from it first
+            owner: Owner<S>,
         }
 
         impl OwningParser {
             pub fn new<T: Into<RefCount<str>>>(user_agent: T) -> Self {
-                OwningParser {
-                    ua: user_agent.into(),
+                Self::with_database(user_agent, UA_PARSER_REGEXES.clone())
+            }
+
+            /// Creates a parser using the rules of `db` instead of the
+            /// built-in database.
+            pub fn with_database<T: Into<RefCount<str>>>(
+                user_agent: T,
+                db: Arc<RegexDatabase>,
+            ) -> Self {
+                match Self::from_owner_with_database(user_agent.into(), db) {
+                    Ok(parser) => parser,
+                    Err(_) => unreachable!("a str is valid UTF-8"),
+                }
+            }
+        }
+
+        impl<S> OwningParser<S>
+        where
+            S: StableDeref,
+            S::Target: AsRef<[u8]>,
+        {
+            /// Creates a parser owning the user agent `owner`, e.g. a
+            /// `Box<str>` or a `Vec<u8>`. Fails if the user agent is not
+            /// valid UTF-8.
+            pub fn from_owner(owner: S) -> Result<Self, Utf8Error> {
+                Self::from_owner_with_database(owner, UA_PARSER_REGEXES.clone())
+            }
+
+            /// Like `from_owner`, but using the rules of `db`.
+            pub fn from_owner_with_database(
+                owner: S,
+                db: Arc<RegexDatabase>,
+            ) -> Result<Self, Utf8Error> {
+                Ok(OwningParser {
                     browser: OnceCell::INIT,
                     device: OnceCell::INIT,
                     os: OnceCell::INIT,
-                }
+                    engine: OnceCell::INIT,
+                    webview: OnceCell::INIT,
+                    owner: Owner::new(owner, db)?,
+                })
             }
 
             pub fn user_agent(&self) -> &str {
-                &*self.ua
+                self.owner.user_agent()
             }
 
-            pub fn browser(&self) -> &Browser {
+            // The results live as long as `owner`, but are only handed out
+            // with the lifetime of `self`.
+
+            pub fn browser(&self) -> &Browser<'_> {
                 self.browser
-                    .get_or_init(|| owned::Browser::new(self.ua.clone(), |ua| ua.into()))
-                    .suffix()
+                    .get_or_init(|| self.owner.db().browser(self.owner.user_agent()))
             }
 
-            pub fn device(&self) -> &Device {
+            pub fn device(&self) -> &Device<'_> {
                 self.device
-                    .get_or_init(|| owned::Device::new(self.ua.clone(), |ua| ua.into()))
-                    .suffix()
+                    .get_or_init(|| self.owner.db().device(self.owner.user_agent()))
             }
 
-            pub fn os(&self) -> &OS {
+            pub fn os(&self) -> &OS<'_> {
                 self.os
-                    .get_or_init(|| owned::OS::new(self.ua.clone(), |ua| ua.into()))
-                    .suffix()
+                    .get_or_init(|| self.owner.db().os(self.owner.user_agent()))
+            }
+
+            pub fn engine(&self) -> &Engine<'_> {
+                self.engine
+                    .get_or_init(|| parse_engine(self.owner.user_agent()))
+            }
+
+            /// Returns the in-app browser or WebView, see
+            /// `BorrowingParser::webview`.
+            pub fn webview(&self) -> Option<&WebView<'_>> {
+                self.webview
+                    .get_or_init(|| parse_webview(self.owner.user_agent()))
+                    .as_ref()
+            }
+
+            pub fn is_webview(&self) -> bool {
+                self.webview().is_some()
+            }
+
+            /// Reports which rules determined the results, see `Explanation`.
+            pub fn explain(&self) -> Explanation<'_> {
+                self.owner.db().explain(self.owner.user_agent())
             }
 
+            /// Returns `true` if the device is "Spider", see
+            /// `BorrowingParser::is_bot`.
             pub fn is_bot(&self) -> bool {
-                self.device().family == "Spider"
+                if let Some(device) = self.device.get() {
+                    return device.family == "Spider";
+                }
+                let db = self.owner.db();
+                match db.check_bot(self.owner.user_agent()) {
+                    Verdict::Human => false,
+                    Verdict::Bot(device) => {
+                        let _ = self.device.set(device);
+                        true
+                    }
+                    Verdict::Unsure => self.device().family == "Spider",
+                }
             }
         }
 
-        impl UserAgentInformation for OwningParser {
+        impl<S> UserAgentInformation for OwningParser<S>
+        where
+            S: StableDeref,
+            S::Target: AsRef<[u8]>,
+        {
             fn user_agent(&self) -> &str {
                 OwningParser::user_agent(self)
             }
 
-            fn browser(&self) -> &Browser {
+            fn browser(&self) -> &Browser<'_> {
                 OwningParser::browser(self)
             }
 
-            fn device(&self) -> &Device {
+            fn device(&self) -> &Device<'_> {
                 OwningParser::device(self)
             }
 
-            fn os(&self) -> &OS {
+            fn os(&self) -> &OS<'_> {
                 OwningParser::os(self)
             }
 
+            fn engine(&self) -> &Engine<'_> {
+                OwningParser::engine(self)
+            }
+
             fn is_bot(&self) -> bool {
                 OwningParser::is_bot(self)
             }
+
+            fn webview(&self) -> Option<&WebView<'_>> {
+                OwningParser::webview(self)
+            }
         }
 
         #[test]
@@ -212,6 +499,23 @@ macro_rules! create_parser {
             }
         }
 
+        #[test]
+        fn test_from_owner() {
+            let agent = "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0";
+            let boxed: Box<str> = agent.into();
+            let parser = OwningParser::from_owner(boxed).unwrap();
+            let browser = parser.browser().clone().into_owned();
+            let parser = vec![parser].pop().unwrap();
+            assert_eq!(parser.browser(), &browser);
+            assert_eq!(parser.user_agent(), agent);
+            let bytes = agent.as_bytes().to_vec();
+            assert_eq!(
+                OwningParser::from_owner(bytes).unwrap().os().family,
+                "Linux"
+            );
+            assert!(OwningParser::from_owner(vec![0xff, 0xfe]).is_err());
+        }
+
         #[test]
         fn test_user_agent() {
             assert_eq!(
@@ -220,12 +524,38 @@ macro_rules! create_parser {
             );
             assert_eq!(OwningParser::new("Samba 1234").user_agent(), "Samba 1234");
         }
+
+        #[test]
+        fn test_engine() {
+            let agent = "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0";
+            assert_eq!(BorrowingParser::new(agent).engine().family, "Gecko");
+            assert_eq!(OwningParser::new(agent).engine().major_or(0), 60);
+        }
+
+        #[test]
+        fn test_webview() {
+            let agent = "Mozilla/5.0 (Linux; Android 13; Pixel 7 Build/TQ3A.230805.001; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/116.0.0.0 Mobile Safari/537.36 Instagram 298.0.0.31.110 Android";
+            let app = BorrowingParser::new(agent).webview().unwrap().to_static();
+            assert_eq!(app.app.as_deref(), Some("Instagram"));
+            assert_eq!(OwningParser::new(agent).webview(), Some(&app));
+            assert!(!OwningParser::new("Firefox").is_webview());
+        }
+
+        #[test]
+        fn test_with_database() {
+            let db = RegexDatabase::builtin();
+            let agent = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+            assert!(BorrowingParser::with_database(agent, &db).is_bot());
+            assert!(OwningParser::with_database(agent, db.clone()).is_bot());
+        }
     };
 }
 
 pub mod sync {
     create_parser! {std::sync::Arc, once_cell::sync::OnceCell}
 
+    pub use crate::cache::{CacheStats, ParserCache};
+
     #[test]
     fn test_sync() {
         use std::sync::Arc;
@@ -253,6 +583,41 @@ pub mod sync {
     }
 }
 
+#[cfg(feature = "bytes")]
+#[test]
+fn test_bytes_owner() {
+    let bytes = bytes::Bytes::from_static(
+        b"Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0",
+    );
+    let parser = sync::OwningParser::from_owner(StableBytes(bytes)).unwrap();
+    let parser = std::thread::spawn(move || parser).join().unwrap();
+    assert_eq!(parser.browser().family, "Firefox");
+}
+
 pub mod unsync {
     create_parser! {std::rc::Rc, once_cell::unsync::OnceCell}
+
+    #[test]
+    fn test_from_owner_as_ref_once() {
+        use std::cell::Cell;
+
+        const AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0";
+
+        /// Returns valid UTF-8 only on the first call.
+        struct Fickle(Cell<bool>);
+
+        impl AsRef<[u8]> for Fickle {
+            fn as_ref(&self) -> &[u8] {
+                if self.0.replace(true) {
+                    &[0xff, 0xfe]
+                } else {
+                    AGENT.as_bytes()
+                }
+            }
+        }
+
+        let parser = OwningParser::from_owner(Box::new(Fickle(Cell::new(false)))).unwrap();
+        assert_eq!(parser.user_agent(), AGENT);
+        assert_eq!(parser.browser().family, "Firefox");
+    }
 }
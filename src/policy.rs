@@ -0,0 +1,399 @@
+//! Browser support policies.
+//!
+//! A policy is a comma separated list of rules in the style of
+//! [browserslist](https://github.com/browserslist/browserslist), e.g.
+//! `Chrome >= 100, Firefox ESR, Safari >= 15.4, not IE`. Like browserslist
+//! the rules are applied in order: a rule allows the matching user agents,
+//! a rule starting with `not` excludes them again.
+//!
+//! A rule names a browser or operating system family as reported by the
+//! parser, ignoring case, optionally followed by a version condition:
+//!
+//! - `Safari` matches every version,
+//! - `Safari 15` matches every version starting with `15`,
+//! - `Safari >= 15.4` compares the version, also `>`, `<=`, `<` and `=`,
+//! - `Firefox ESR` matches Firefox versions whose major version has an extended
+//!   support release. The user agent does not tell an ESR build apart from a
+//!   regular release of the same major version, so both match.
+//!
+//! ```rust
+//! use uap_rust::policy::Policy;
+//! let policy: Policy = "Chrome >= 100, Firefox ESR, Safari >= 15.4, not IE".parse().unwrap();
+//! let result = policy.evaluate_user_agent("Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0");
+//! assert!(result.allowed);
+//! assert_eq!(result.rule.unwrap().to_string(), "Firefox ESR");
+//! assert_eq!(result.reason, "Firefox 60.0 has major version 60, which has an ESR branch");
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use crate::unsync::BorrowingParser;
+use crate::{UaVersion, UserAgentInformation};
+
+/// Major versions of Firefox with an extended support release.
+static FIREFOX_ESR: &[u64] = &[
+    10, 17, 24, 31, 38, 45, 52, 60, 68, 78, 91, 102, 115, 128, 140,
+];
+
+/// Names used by browserslist and the corresponding uap-core family.
+static ALIASES: &[(&str, &str)] = &[
+    ("explorer", "IE"),
+    ("ie", "IE"),
+    ("chromeandroid", "Chrome Mobile"),
+    ("and_chr", "Chrome Mobile"),
+    ("firefoxandroid", "Firefox Mobile"),
+    ("and_ff", "Firefox Mobile"),
+    ("ios_saf", "Mobile Safari"),
+    ("ios safari", "Mobile Safari"),
+    ("samsung", "Samsung Internet"),
+];
+
+/// A version comparison of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Eq => "=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Any,
+    Version(UaVersion),
+    Compare(Op, UaVersion),
+    Esr,
+}
+
+/// A single rule of a `Policy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    negated: bool,
+    family: String,
+    condition: Condition,
+}
+
+/// Whether a rule matches a browser or an operating system.
+enum Target<'a> {
+    Browser(&'a str, Option<UaVersion>),
+    OS(&'a str, Option<UaVersion>),
+}
+
+impl Rule {
+    /// Returns `true` for rules starting with `not`.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Returns the browser or operating system family of the rule.
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    /// Returns the reason why the rule matches `info`, if it does.
+    fn matches<P: UserAgentInformation + ?Sized>(&self, info: &P) -> Option<String> {
+        let browser = info.browser();
+        let target = if browser.family.eq_ignore_ascii_case(&self.family) {
+            Target::Browser(&browser.family, browser.version())
+        } else {
+            let os = info.os();
+            if !os.family.eq_ignore_ascii_case(&self.family) {
+                return None;
+            }
+            Target::OS(&os.family, os.version())
+        };
+        let (kind, family, version) = match &target {
+            Target::Browser(family, version) => ("browser", family, version),
+            Target::OS(family, version) => ("operating system", family, version),
+        };
+        let described = || match version {
+            Some(version) => format!("{} {}", family, version),
+            None => family.to_string(),
+        };
+        let matched = match &self.condition {
+            Condition::Any => return Some(format!("{} is {}", kind, family)),
+            Condition::Version(expected) => version.as_ref()?.starts_with(expected),
+            Condition::Compare(op, expected) => {
+                let version = version.as_ref()?;
+                match op {
+                    Op::Lt => version < expected,
+                    Op::Le => version <= expected,
+                    Op::Gt => version > expected,
+                    Op::Ge => version >= expected,
+                    Op::Eq => version == expected,
+                }
+            }
+            Condition::Esr => {
+                let is_firefox = matches!(target, Target::Browser(..)) && *family == "Firefox";
+                let version = version.as_ref()?;
+                if is_firefox && FIREFOX_ESR.contains(&version.major()) {
+                    return Some(format!(
+                        "{} has major version {}, which has an ESR branch",
+                        described(),
+                        version.major()
+                    ));
+                }
+                false
+            }
+        };
+        if !matched {
+            return None;
+        }
+        Some(match &self.condition {
+            Condition::Compare(op, expected) => {
+                format!("{} {} {}", described(), op.as_str(), expected)
+            }
+            _ => format!("{} matches {}", described(), self.condition_text()),
+        })
+    }
+
+    fn condition_text(&self) -> String {
+        match &self.condition {
+            Condition::Any => self.family.clone(),
+            Condition::Version(version) => format!("{} {}", self.family, version),
+            Condition::Compare(op, version) => {
+                format!("{} {} {}", self.family, op.as_str(), version)
+            }
+            Condition::Esr => format!("{} ESR", self.family),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("not ")?;
+        }
+        f.write_str(&self.condition_text())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParsePolicyError;
+
+    fn from_str(rule: &str) -> Result<Rule, ParsePolicyError> {
+        let error = |message| ParsePolicyError {
+            rule: rule.trim().into(),
+            message,
+        };
+        let mut text = rule.trim();
+        let negated = text
+            .get(..4)
+            .is_some_and(|not| not.eq_ignore_ascii_case("not "));
+        if negated {
+            text = text[4..].trim_start();
+        }
+        let version = |version: &str| {
+            version
+                .parse::<UaVersion>()
+                .ok()
+                .filter(|v| !v.components().is_empty())
+                .ok_or_else(|| error("invalid version"))
+        };
+        let operators = [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("=", Op::Eq),
+        ];
+        let operator = operators
+            .iter()
+            .find_map(|&(token, op)| text.find(token).map(|i| (i, token, op)));
+        let (family, condition) = if let Some((i, token, op)) = operator {
+            let family = text[..i].trim();
+            (
+                family,
+                Condition::Compare(op, version(&text[i + token.len()..])?),
+            )
+        } else {
+            match text.rfind(' ') {
+                Some(i) if text[i + 1..].eq_ignore_ascii_case("esr") => {
+                    (text[..i].trim(), Condition::Esr)
+                }
+                Some(i) if text[i + 1..].starts_with(|c: char| c.is_ascii_digit()) => (
+                    text[..i].trim(),
+                    Condition::Version(version(&text[i + 1..])?),
+                ),
+                _ => (text, Condition::Any),
+            }
+        };
+        if family.is_empty() {
+            return Err(error("missing browser or operating system"));
+        }
+        let family = ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(family))
+            .map_or(family, |(_, family)| family);
+        Ok(Rule {
+            negated,
+            family: family.into(),
+            condition,
+        })
+    }
+}
+
+/// A list of rules deciding which browsers are supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+/// The result of evaluating a `Policy` for a user agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation<'p> {
+    /// Whether the user agent is supported.
+    pub allowed: bool,
+    /// The rule which decided, `None` if no rule matched.
+    pub rule: Option<&'p Rule>,
+    /// The index of `rule` in the policy.
+    pub index: Option<usize>,
+    /// A description of why the rule matched.
+    pub reason: String,
+}
+
+impl Policy {
+    /// Returns the rules of the policy.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Evaluates the policy for the parsed user agent `info`.
+    pub fn evaluate<P: UserAgentInformation + ?Sized>(&self, info: &P) -> Evaluation<'_> {
+        let mut evaluation = Evaluation {
+            allowed: false,
+            rule: None,
+            index: None,
+            reason: "no rule matches".into(),
+        };
+        for (index, rule) in self.rules.iter().enumerate() {
+            // Rules which would not change the result are skipped
+            if rule.negated != evaluation.allowed {
+                continue;
+            }
+            if let Some(reason) = rule.matches(info) {
+                evaluation = Evaluation {
+                    allowed: !rule.negated,
+                    rule: Some(rule),
+                    index: Some(index),
+                    reason,
+                };
+            }
+        }
+        evaluation
+    }
+
+    /// Parses `user_agent` and evaluates the policy for it.
+    pub fn evaluate_user_agent(&self, user_agent: &str) -> Evaluation<'_> {
+        self.evaluate(&BorrowingParser::new(user_agent))
+    }
+}
+
+impl FromStr for Policy {
+    type Err = ParsePolicyError;
+
+    /// Parses rules separated by commas or `or`.
+    fn from_str(policy: &str) -> Result<Policy, ParsePolicyError> {
+        let rules = policy
+            .split(',')
+            .flat_map(|part| part.split(" or "))
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Policy { rules })
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing a `Policy` fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePolicyError {
+    rule: String,
+    message: &'static str,
+}
+
+impl fmt::Display for ParsePolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid rule `{}`: {}", self.rule, self.message)
+    }
+}
+
+impl std::error::Error for ParsePolicyError {}
+
+#[test]
+fn test_parse() {
+    let policy: Policy = "Chrome >= 100, firefox esr,Safari 15 or not explorer"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        policy.to_string(),
+        "Chrome >= 100, firefox ESR, Safari 15, not IE"
+    );
+    assert!(policy.rules()[3].is_negated());
+    assert!("Chrome >=".parse::<Policy>().is_err());
+    assert!("Chrome >= x".parse::<Policy>().is_err());
+    assert!(">= 100".parse::<Policy>().is_err());
+    assert!("Chrome,".parse::<Policy>().is_err());
+    assert!("nöt".parse::<Policy>().is_ok());
+}
+
+#[test]
+fn test_evaluate() {
+    let policy: Policy = "Chrome >= 100, Firefox ESR, Safari >= 15.4, iOS >= 15, not IE"
+        .parse()
+        .unwrap();
+    let evaluate = |agent| {
+        let evaluation = policy.evaluate_user_agent(agent);
+        (evaluation.allowed, evaluation.index, evaluation.reason)
+    };
+    assert_eq!(
+        evaluate("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.5481.100 Safari/537.36"),
+        (true, Some(0), "Chrome 110.0.5481 >= 100".into())
+    );
+    assert_eq!(
+        evaluate("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/99.0.4844.51 Safari/537.36"),
+        (false, None, "no rule matches".into())
+    );
+    assert_eq!(
+        evaluate("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/15.3 Safari/605.1.15"),
+        (false, None, "no rule matches".into())
+    );
+    assert_eq!(
+        evaluate("Mozilla/5.0 (iPhone; CPU iPhone OS 16_3 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.3 Mobile/15E148 Safari/604.1"),
+        (true, Some(3), "iOS 16.3 >= 15".into())
+    );
+    assert_eq!(
+        evaluate("Mozilla/5.0 (X11; Linux x86_64; rv:61.0) Gecko/20100101 Firefox/61.0"),
+        (false, None, "no rule matches".into())
+    );
+
+    let policy: Policy = "Chrome, IE >= 9, not IE 9".parse().unwrap();
+    let evaluation = policy
+        .evaluate_user_agent("Mozilla/5.0 (compatible; MSIE 9.0; Windows NT 6.1; Trident/5.0)");
+    assert!(!evaluation.allowed);
+    assert_eq!(evaluation.rule.unwrap().to_string(), "not IE 9");
+    assert_eq!(evaluation.reason, "IE 9.0 matches IE 9");
+}
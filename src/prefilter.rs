@@ -0,0 +1,233 @@
+//! Literal prefilter for the rules of a category.
+//!
+//! Most rules can only match if the user agent contains one of a few
+//! literal strings, e.g. `(?i)(Nexus \d+)` needs `nexus `. These literals
+//! are extracted from the parsed patterns and searched for with a single
+//! Aho-Corasick automaton. Only rules whose literals were found (or which
+//! have no usable literals at all) need to run their regular expression.
+use std::collections::HashMap;
+
+use aho_corasick::AhoCorasick;
+use regex_syntax::hir::{Class, Hir, HirKind};
+use regex_syntax::Parser;
+
+/// Literals shorter than this occur in almost every user agent.
+const MIN_LITERAL_LEN: usize = 2;
+
+/// Upper bound on the literals a single rule may require.
+const MAX_ALTERNATIVES: usize = 256;
+
+/// Character classes with more characters are not expanded into literals.
+const MAX_CLASS_LEN: usize = 4;
+
+/// Character classes with more code points are not expanded at all, even if
+/// they would collapse to few characters when lower cased.
+const MAX_CLASS_SIZE: usize = 3 * MAX_CLASS_LEN;
+
+/// One of these literals must occur in the user agent, ignoring ASCII case.
+type Requirement = Vec<Vec<u8>>;
+
+pub(crate) struct Prefilter {
+    matcher: Option<AhoCorasick>,
+    literal_count: usize,
+    /// For each rule the ids of its literals, `None` if the rule has to be
+    /// tried for every user agent.
+    rules: Vec<Option<Vec<usize>>>,
+}
+
+impl Prefilter {
+    pub(crate) fn new<'a, I>(patterns: I) -> Prefilter
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut literals = Vec::new();
+        let mut ids = HashMap::new();
+        let rules = patterns
+            .into_iter()
+            .map(|pattern| {
+                let requirement = Parser::new()
+                    .parse(pattern)
+                    .ok()
+                    .and_then(|hir| required(&hir))?;
+                let rule_ids = requirement
+                    .into_iter()
+                    .map(|literal| {
+                        *ids.entry(literal.clone()).or_insert_with(|| {
+                            literals.push(literal);
+                            literals.len() - 1
+                        })
+                    })
+                    .collect();
+                Some(rule_ids)
+            })
+            .collect();
+        let matcher = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&literals)
+            .ok();
+        Prefilter {
+            matcher,
+            literal_count: literals.len(),
+            rules,
+        }
+    }
+
+    /// Returns the indices of the rules which may match `agent`, in order.
+    pub(crate) fn candidates<'s>(&'s self, agent: &str) -> impl Iterator<Item = usize> + 's {
+        let found = self.matcher.as_ref().map(|matcher| {
+            let mut found = vec![false; self.literal_count];
+            for m in matcher.find_overlapping_iter(agent) {
+                found[m.pattern().as_usize()] = true;
+            }
+            found
+        });
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(move |(_, ids)| match (ids, &found) {
+                (Some(ids), Some(found)) => ids.iter().any(|&id| found[id]),
+                _ => true,
+            })
+            .map(|(i, _)| i)
+    }
+}
+
+/// Computes literals one of which occurs in every match of `hir`.
+fn required(hir: &Hir) -> Option<Requirement> {
+    match hir.kind() {
+        HirKind::Literal(_) | HirKind::Class(_) => literal(hir).filter(usable),
+        HirKind::Capture(capture) => required(&capture.sub),
+        HirKind::Repetition(repetition) if repetition.min > 0 => required(&repetition.sub),
+        HirKind::Concat(children) => {
+            let mut best = None;
+            let mut run = vec![Vec::new()];
+            for child in children {
+                if let Some(variants) = literal(child) {
+                    if run.len() * variants.len() > MAX_ALTERNATIVES {
+                        best = better(best, Some(run).filter(usable));
+                        run = vec![Vec::new()];
+                    }
+                    run = run
+                        .iter()
+                        .flat_map(|prefix| {
+                            variants
+                                .iter()
+                                .map(move |variant| [&prefix[..], variant].concat())
+                        })
+                        .collect();
+                    continue;
+                }
+                match child.kind() {
+                    // Zero-width, so the literals around it are adjacent
+                    HirKind::Look(_) | HirKind::Empty => continue,
+                    _ => (),
+                }
+                best = better(best, Some(run).filter(usable));
+                best = better(best, required(child));
+                run = vec![Vec::new()];
+            }
+            better(best, Some(run).filter(usable))
+        }
+        HirKind::Alternation(children) => {
+            let mut alternatives = Vec::new();
+            for child in children {
+                alternatives.extend(required(child)?);
+            }
+            alternatives.sort();
+            alternatives.dedup();
+            Some(alternatives).filter(|a| a.len() <= MAX_ALTERNATIVES)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the strings matched by `hir`, if there are only a few. ASCII
+/// letters are lower cased.
+fn literal(hir: &Hir) -> Option<Vec<Vec<u8>>> {
+    let mut variants: Vec<Vec<u8>> = match hir.kind() {
+        HirKind::Literal(literal) => vec![literal.0.to_ascii_lowercase()],
+        // Mostly case insensitive letters like `[Kk\u{212A}]`. Every variant
+        // is needed, so large classes are given up on before expanding them.
+        HirKind::Class(Class::Unicode(class)) => {
+            let len: usize = class.iter().map(|range| range.len()).sum();
+            if len > MAX_CLASS_SIZE {
+                return None;
+            }
+            class
+                .iter()
+                .flat_map(|range| range.start()..=range.end())
+                .map(|c| c.to_ascii_lowercase().to_string().into_bytes())
+                .collect()
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            let len: usize = class.iter().map(|range| range.len()).sum();
+            if len > MAX_CLASS_SIZE {
+                return None;
+            }
+            class
+                .iter()
+                .flat_map(|range| range.start()..=range.end())
+                .map(|b| vec![b.to_ascii_lowercase()])
+                .collect()
+        }
+        _ => return None,
+    };
+    variants.sort();
+    variants.dedup();
+    Some(variants).filter(|v| v.len() <= MAX_CLASS_LEN)
+}
+
+fn usable(requirement: &Requirement) -> bool {
+    requirement.iter().all(|l| l.len() >= MIN_LITERAL_LEN)
+}
+
+/// Picks the requirement which is more selective.
+fn better(a: Option<Requirement>, b: Option<Requirement>) -> Option<Requirement> {
+    let score = |r: &Requirement| {
+        let shortest = r.iter().map(Vec::len).min().unwrap_or(0);
+        (shortest.min(8), usize::MAX - r.len())
+    };
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if score(&b) > score(&a) { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+#[test]
+fn test_required() {
+    let required = |pattern| required(&Parser::new().parse(pattern).unwrap());
+    assert_eq!(required(r"; *(Nexus \d+)"), Some(vec![b"nexus ".to_vec()]));
+    assert_eq!(required(r"(?i)Foo\b/Bar"), Some(vec![b"foo/bar".to_vec()]));
+    assert_eq!(
+        required(r"(Foo|Bar)/(\d+)"),
+        Some(vec![b"bar".to_vec(), b"foo".to_vec()])
+    );
+    assert_eq!(required(r"(Foo)?/(\d+)"), None);
+    assert_eq!(required(r"\d+x\d+"), None);
+    assert_eq!(
+        required(r"(?i)ks"),
+        Some(vec![
+            b"ks".to_vec(),
+            "k\u{17F}".into(),
+            "\u{212A}s".into(),
+            "\u{212A}\u{17F}".into(),
+        ])
+    );
+    assert_eq!(
+        required(r"(?i)[ab]cd"),
+        Some(vec![b"acd".to_vec(), b"bcd".to_vec()])
+    );
+    assert_eq!(required(r"[A-Da-z]xy"), Some(vec![b"xy".to_vec()]));
+}
+
+#[test]
+fn test_candidates() {
+    let prefilter = Prefilter::new(vec![r"Foo/(\d+)", r"(\d+)", r"(?i)bar"]);
+    let candidates = |agent| prefilter.candidates(agent).collect::<Vec<_>>();
+    assert_eq!(candidates("Foo/1 BAR"), vec![0, 1, 2]);
+    assert_eq!(candidates("foo/1"), vec![0, 1]);
+    assert_eq!(candidates("Baz"), vec![1]);
+
+    let prefilter = Prefilter::new(vec![r"[A-Da-z]x"]);
+    assert_eq!(prefilter.candidates("zx").collect::<Vec<_>>(), vec![0]);
+}
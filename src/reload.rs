@@ -0,0 +1,293 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use arc_swap::ArcSwap;
+
+use crate::sync::OwningParser;
+use crate::{Category, Error, RegexDatabase};
+
+type Callback = Arc<dyn Fn(&ReloadEvent<'_>) + Send + Sync>;
+
+/// A `RegexDatabase` that can be replaced while it is in use.
+///
+/// `load` returns a snapshot of the current database without blocking.
+/// Parsers created from a snapshot keep using it, a reload only affects
+/// parsers created afterwards. The database can be reloaded from a file on
+/// demand or whenever the file changes, see `watch`.
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use uap_rust::{ReloadEvent, ReloadableDatabase};
+///
+/// let db = Arc::new(ReloadableDatabase::from_file("regexes.yaml").unwrap());
+/// db.on_reload(|event| match event {
+///     ReloadEvent::Loaded(counts) => println!("loaded {} browser rules", counts.browser),
+///     ReloadEvent::Failed(err) => eprintln!("keeping the old rules: {}", err),
+/// });
+/// let _watch = db.watch("regexes.yaml", Duration::from_secs(30));
+/// let parser = db.parser("Mozilla/5.0 ...");
+/// ```
+pub struct ReloadableDatabase {
+    current: ArcSwap<RegexDatabase>,
+    callbacks: Mutex<Vec<Callback>>,
+}
+
+/// The number of rules of each category of a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleCounts {
+    pub browser: usize,
+    pub os: usize,
+    pub device: usize,
+}
+
+impl RuleCounts {
+    fn of(db: &RegexDatabase) -> RuleCounts {
+        RuleCounts {
+            browser: db.rule_count(Category::Browser),
+            os: db.rule_count(Category::OS),
+            device: db.rule_count(Category::Device),
+        }
+    }
+}
+
+/// Outcome of a reload, passed to the callbacks of `on_reload`.
+#[derive(Debug)]
+pub enum ReloadEvent<'a> {
+    /// A new database was loaded and is used from now on.
+    Loaded(RuleCounts),
+    /// Loading failed, the previous database is still used.
+    Failed(&'a Error),
+}
+
+impl ReloadableDatabase {
+    /// Starts with the database `db`.
+    pub fn new(db: Arc<RegexDatabase>) -> Self {
+        ReloadableDatabase {
+            current: ArcSwap::new(db),
+            callbacks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts with the built-in database.
+    pub fn builtin() -> Self {
+        ReloadableDatabase::new(RegexDatabase::builtin())
+    }
+
+    /// Starts with the database loaded from `path`, see `reload_from_file`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(ReloadableDatabase::new(Arc::new(read_file(path.as_ref())?)))
+    }
+
+    /// Returns the current database.
+    pub fn load(&self) -> Arc<RegexDatabase> {
+        self.current.load_full()
+    }
+
+    /// Returns a parser for `user_agent` using the current database.
+    pub fn parser<T: Into<Arc<str>>>(&self, user_agent: T) -> OwningParser {
+        OwningParser::with_database(user_agent, self.load())
+    }
+
+    /// Replaces the database and notifies the callbacks.
+    pub fn store(&self, db: Arc<RegexDatabase>) {
+        let counts = RuleCounts::of(&db);
+        self.current.store(db);
+        self.notify(&ReloadEvent::Loaded(counts));
+    }
+
+    /// Loads the database from `path` and replaces the current one. Files
+    /// ending in `.yaml` or `.yml` are read as uap-core's `regexes.yaml`,
+    /// others as MessagePack.
+    ///
+    /// On failure the current database is kept. In both cases the callbacks
+    /// are notified.
+    pub fn reload_from_file<P: AsRef<Path>>(&self, path: P) -> Result<RuleCounts, Error> {
+        match read_file(path.as_ref()) {
+            Ok(db) => {
+                let counts = RuleCounts::of(&db);
+                self.store(Arc::new(db));
+                Ok(counts)
+            }
+            Err(err) => {
+                self.notify(&ReloadEvent::Failed(&err));
+                Err(err)
+            }
+        }
+    }
+
+    /// Registers `callback` to be called after each reload. Callbacks may
+    /// register further callbacks or reload the database themselves.
+    pub fn on_reload<F>(&self, callback: F)
+    where
+        F: Fn(&ReloadEvent<'_>) + Send + Sync + 'static,
+    {
+        self.lock_callbacks().push(Arc::new(callback));
+    }
+
+    /// Checks the modification time of `path` every `interval` and reloads
+    /// the database from it when it changed. The file is watched until the
+    /// returned `FileWatch` is dropped.
+    pub fn watch<P: Into<PathBuf>>(self: &Arc<Self>, path: P, interval: Duration) -> FileWatch {
+        let path = path.into();
+        let db = Arc::downgrade(self);
+        let (stop, stopped) = mpsc::channel();
+        // Before spawning, so changes right after `watch` returns are seen
+        let mut last = modified(&path);
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let db = match db.upgrade() {
+                    Some(db) => db,
+                    None => break,
+                };
+                // The file may be missing briefly while it is replaced
+                let current = match modified(&path) {
+                    Some(current) => current,
+                    None => continue,
+                };
+                if last != Some(current) {
+                    last = Some(current);
+                    let _ = db.reload_from_file(&path);
+                }
+            }
+        });
+        FileWatch {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    fn notify(&self, event: &ReloadEvent) {
+        // Not called under the lock, a callback may call `on_reload` itself
+        let callbacks = self.lock_callbacks().clone();
+        for callback in callbacks {
+            callback(event);
+        }
+    }
+
+    fn lock_callbacks(&self) -> std::sync::MutexGuard<'_, Vec<Callback>> {
+        self.callbacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for ReloadableDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableDatabase")
+            .field("rules", &RuleCounts::of(&self.current.load()))
+            .finish()
+    }
+}
+
+/// Watches a file for a `ReloadableDatabase`. Watching stops when it is
+/// dropped.
+#[derive(Debug)]
+pub struct FileWatch {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for FileWatch {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<RegexDatabase, Error> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => RegexDatabase::from_yaml_file(path),
+        _ => RegexDatabase::from_file(path),
+    }
+}
+
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+fn rules_yaml(family: &str) -> String {
+    format!(
+        "user_agent_parsers:\n  - regex: '(Foo)/(\\d+)'\n    family_replacement: '{}'\nos_parsers: []\ndevice_parsers: []\n",
+        family
+    )
+}
+
+#[test]
+fn test_reload_from_file() {
+    let dir = std::env::temp_dir().join(format!("uap-reload-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("regexes.yaml");
+    fs::write(&path, rules_yaml("Foo 1")).unwrap();
+
+    let db = ReloadableDatabase::from_file(&path).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let log = events.clone();
+    db.on_reload(move |event| log.lock().unwrap().push(format!("{:?}", event)));
+
+    let old = db.parser("Foo/1");
+    fs::write(&path, rules_yaml("Foo 2")).unwrap();
+    let counts = db.reload_from_file(&path).unwrap();
+    assert_eq!((counts.browser, counts.os, counts.device), (1, 0, 0));
+    assert_eq!(old.browser().family, "Foo 1");
+    assert_eq!(db.parser("Foo/1").browser().family, "Foo 2");
+
+    fs::write(&path, "user_agent_parsers:\n  - regex: '(Foo'\n").unwrap();
+    assert!(db.reload_from_file(&path).is_err());
+    assert_eq!(db.parser("Foo/1").browser().family, "Foo 2");
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(events[0].starts_with("Loaded"), "{}", events[0]);
+    assert!(events[1].starts_with("Failed"), "{}", events[1]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_watch() {
+    let dir = std::env::temp_dir().join(format!("uap-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("regexes.yaml");
+    fs::write(&path, rules_yaml("Foo 1")).unwrap();
+
+    let db = Arc::new(ReloadableDatabase::from_file(&path).unwrap());
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    db.on_reload(move |event| {
+        let _ = sender
+            .lock()
+            .unwrap()
+            .send(matches!(event, ReloadEvent::Loaded(_)));
+    });
+    let watch = db.watch(&path, Duration::from_millis(10));
+    // Differs in length, in case the modification time is coarse
+    fs::write(&path, rules_yaml("Foo 22")).unwrap();
+    assert!(events.recv_timeout(Duration::from_secs(10)).unwrap());
+    assert_eq!(db.load().browser("Foo/1").family, "Foo 22");
+    drop(watch);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reentrant_callback() {
+    let db = Arc::new(ReloadableDatabase::builtin());
+    let calls = Arc::new(Mutex::new(0));
+    let (weak, count) = (Arc::downgrade(&db), calls.clone());
+    db.on_reload(move |_| {
+        let count = count.clone();
+        if let Some(db) = weak.upgrade() {
+            db.on_reload(move |_| *count.lock().unwrap() += 1);
+        }
+    });
+    db.store(RegexDatabase::builtin());
+    assert_eq!(*calls.lock().unwrap(), 0);
+    db.store(RegexDatabase::builtin());
+    assert_eq!(*calls.lock().unwrap(), 1);
+}
@@ -1,32 +1,924 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
 
-use regex::{Captures, Regex, RegexBuilder};
-
-use serde::de::Error;
-use serde::{Deserialize, Deserializer};
-use serde_derive::Deserialize;
+use regex::Captures;
 
 use lazy_static::lazy_static;
 
+use once_cell::sync::OnceCell;
+
 use rmp_serde as rmps;
 
-use crate::{Browser, Device, DEFAULT_NAME, OS};
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+
+use crate::batch::Batch;
+use crate::bot::{BotMatcher, SpiderRule, Verdict};
+use crate::matcher::Matcher;
+#[cfg(feature = "prebuilt")]
+use crate::matcher::{self, Dfa};
+use crate::prefilter::Prefilter;
+use crate::{Browser, Device, Error, Explanation, RuleError, RuleMatch, DEFAULT_NAME, OS};
 
-static UA_PARSER_REGEX_DATA: &'static [u8] = include_bytes!("../resources/regexes.msgpack");
+static UA_PARSER_REGEX_DATA: &[u8] = include_bytes!("../resources/regexes.msgpack");
+
+static BUILTIN: OnceCell<Arc<RegexDatabase>> = OnceCell::INIT;
 
 lazy_static! {
-    pub(super) static ref UA_PARSER_REGEXES: UARegexes<'static> =
-        { rmps::from_slice(UA_PARSER_REGEX_DATA).unwrap() };
+    pub(super) static ref UA_PARSER_REGEXES: Arc<RegexDatabase> = RegexDatabase::load()
+        .unwrap_or_else(|err| panic!("could not load the built-in regex database: {}", err));
+}
+
+/// The category of a rule, which determines what it detects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Category {
+    Browser,
+    OS,
+    Device,
+}
+
+impl Category {
+    /// Returns the name of the category in lower case, e.g. `"browser"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Browser => "browser",
+            Category::OS => "os",
+            Category::Device => "device",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where a rule of a `RegexDatabase` comes from.
+///
+/// A database built with `DatabaseBuilder` tries the override rules first,
+/// then the rules of the base database and the fallback rules last. The
+/// first matching rule determines the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Layer {
+    /// Added with `DatabaseBuilder::overrides_yaml`.
+    Override,
+    /// Loaded from `regexes.yaml` or MessagePack, or built-in.
+    Base,
+    /// Added with `DatabaseBuilder::fallbacks_yaml`.
+    Fallback,
+}
+
+impl Layer {
+    /// Returns the name of the layer in lower case, e.g. `"override"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Layer::Override => "override",
+            Layer::Base => "base",
+            Layer::Fallback => "fallback",
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct UARegexes {
+    browser_parsers: Rules<UABrowserRegex>,
+    device_parsers: Rules<UADeviceRegex>,
+    os_parsers: Rules<UAOSRegex>,
+}
+
+trait Rule {
+    fn regex(&self) -> &Matcher;
+
+    /// Returns the replacement templates of the rule by field name.
+    fn templates(&self) -> Vec<(&'static str, &str)>;
+}
+
+/// A rule as it is stored, before its regular expression is compiled.
+trait RawRule {
+    type Rule;
+
+    /// Compiles the rule, on failure returns the pattern and the error.
+    fn compile(self) -> Result<Self::Rule, (String, regex::Error)>;
+
+    /// Creates the rule using the prebuilt `dfa` for matching.
+    #[cfg(feature = "prebuilt")]
+    fn prebuilt(self, dfa: Dfa) -> Self::Rule;
+}
+
+/// A rule of the built-in database with its DFA, if one was prebuilt.
+#[cfg(feature = "prebuilt")]
+struct Prebuilt<R> {
+    raw: R,
+    dfa: Option<Dfa>,
+}
+
+#[cfg(feature = "prebuilt")]
+impl<R: RawRule> RawRule for Prebuilt<R> {
+    type Rule = R::Rule;
+
+    fn compile(self) -> Result<Self::Rule, (String, regex::Error)> {
+        match self.dfa {
+            Some(dfa) => Ok(self.raw.prebuilt(dfa)),
+            None => self.raw.compile(),
+        }
+    }
+
+    fn prebuilt(self, dfa: Dfa) -> Self::Rule {
+        self.raw.prebuilt(dfa)
+    }
+}
+
+/// The rules of one category, which are tried in order.
+///
+/// Instead of running each regular expression until one matches, a
+/// `Prefilter` determines in a single pass over the user agent which rules
+/// can match at all. It is built on first use.
+struct Rules<T> {
+    rules: Vec<T>,
+    /// The layer of each rule and its position in the rules loaded for the
+    /// layer, which differs from the position in `rules` if rules were
+    /// skipped or layered.
+    origins: Vec<(Layer, usize)>,
+    prefilter: OnceCell<Prefilter>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Rules<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.rules.fmt(f)
+    }
+}
+
+impl<T> Rules<T> {
+    /// Compiles the rules of `category`. Broken rules fail the whole
+    /// database, unless `options` is lenient. Then they are skipped and
+    /// added to `diagnostics`.
+    fn compile<R>(
+        category: Category,
+        layer: Layer,
+        raw: Vec<R>,
+        options: &LoadOptions,
+        diagnostics: &mut Vec<RuleError>,
+    ) -> Result<Self, Error>
+    where
+        R: RawRule<Rule = T>,
+    {
+        let mut rules = Vec::with_capacity(raw.len());
+        let mut origins = Vec::with_capacity(raw.len());
+        for (index, raw) in raw.into_iter().enumerate() {
+            match raw.compile() {
+                Ok(rule) => {
+                    rules.push(rule);
+                    origins.push((layer, index));
+                }
+                Err((pattern, error)) => {
+                    let err = RuleError {
+                        category,
+                        index,
+                        pattern,
+                        error,
+                    };
+                    if !options.lenient {
+                        return Err(Error::Rule(err));
+                    }
+                    diagnostics.push(err);
+                }
+            }
+        }
+        Ok(Rules {
+            rules,
+            origins,
+            prefilter: OnceCell::INIT,
+        })
+    }
+
+    /// Returns `overrides`, followed by the rules of `base` for which
+    /// `keep` returns `true` and `fallbacks`.
+    fn layered<F>(overrides: Rules<T>, base: &Rules<T>, mut keep: F, fallbacks: Rules<T>) -> Self
+    where
+        T: Clone,
+        F: FnMut((Layer, usize), &T) -> bool,
+    {
+        let mut rules = overrides.rules;
+        let mut origins = overrides.origins;
+        for (rule, &origin) in base.rules.iter().zip(&base.origins) {
+            if keep(origin, rule) {
+                rules.push(rule.clone());
+                origins.push(origin);
+            }
+        }
+        rules.extend(fallbacks.rules);
+        origins.extend(fallbacks.origins);
+        Rules {
+            rules,
+            origins,
+            prefilter: OnceCell::INIT,
+        }
+    }
+}
+
+impl<T: Rule> Rules<T> {
+    /// Applies `parse` to the rules which may match `agent` and returns the
+    /// first result.
+    fn find_map<'a, R, F>(&'a self, agent: &str, mut parse: F) -> Option<R>
+    where
+        F: FnMut(&'a T) -> Option<R>,
+    {
+        self.find_map_indexed(agent, |_, rule| parse(rule))
+    }
+
+    /// Like `find_map`, but also passes the layer and index of the rule.
+    fn find_map_indexed<'a, R, F>(&'a self, agent: &str, mut parse: F) -> Option<R>
+    where
+        F: FnMut((Layer, usize), &'a T) -> Option<R>,
+    {
+        self.prefilter()
+            .candidates(agent)
+            .find_map(|i| parse(self.origins[i], &self.rules[i]))
+    }
+
+    fn prefilter(&self) -> &Prefilter {
+        self.prefilter
+            .get_or_init(|| Prefilter::new(self.rules.iter().map(|rule| rule.regex().as_str())))
+    }
+
+    /// Compiles all rules and the prefilter. Adds the rules which cannot
+    /// be compiled to `diagnostics`.
+    fn warm_up(&self, category: Category, diagnostics: &mut Vec<RuleError>) {
+        self.prefilter();
+        for (rule, &(_, index)) in self.rules.iter().zip(&self.origins) {
+            if let Err(error) = rule.regex().compile() {
+                diagnostics.push(RuleError {
+                    category,
+                    index,
+                    pattern: rule.regex().as_str().to_string(),
+                    error: error.clone(),
+                });
+            }
+        }
+    }
+
+    /// Returns the first rule matching `agent` with the details of the match.
+    /// `value` computes the result from the captures.
+    fn explain<'a, V, F>(
+        &'a self,
+        category: Category,
+        agent: &'a str,
+        value: F,
+    ) -> Option<RuleMatch<'a, V>>
+    where
+        F: Fn(&'a T, &Captures<'a>) -> V,
+    {
+        self.find_map_indexed(agent, |(layer, index), rule| {
+            let captures = rule.regex().captures(agent)?;
+            Some(RuleMatch {
+                category,
+                layer,
+                index,
+                pattern: rule.regex().as_str(),
+                captures: captures.iter().map(|m| m.map(|m| m.as_str())).collect(),
+                templates: rule.templates(),
+                value: value(rule, &captures),
+            })
+        })
+    }
+}
+
+/// The rules as decoded: the rules of each category with their fields, as
+/// in `build.rs`.
+type RawFile = BTreeMap<String, Vec<RawFields>>;
+
+type RawFields = BTreeMap<String, Option<String>>;
+
+/// The rules before compilation.
+#[derive(Default)]
+struct UARegexesRaw {
+    browser_parsers: Vec<UABrowserRegexRaw>,
+    device_parsers: Vec<UADeviceRegexRaw>,
+    os_parsers: Vec<UAOSRegexRaw>,
+}
+
+impl UARegexesRaw {
+    /// Takes the categories and fields by the short names used by the
+    /// MessagePack format of `resources.py` or by the names of uap-core's
+    /// `regexes.yaml`.
+    fn new(mut file: RawFile) -> Result<UARegexesRaw, Error> {
+        Ok(UARegexesRaw {
+            browser_parsers: raw_rules(
+                &mut file,
+                Category::Browser,
+                ["b", "user_agent_parsers"],
+                UABrowserRegexRaw::new,
+            )?,
+            device_parsers: raw_rules(
+                &mut file,
+                Category::Device,
+                ["d", "device_parsers"],
+                UADeviceRegexRaw::new,
+            )?,
+            os_parsers: raw_rules(
+                &mut file,
+                Category::OS,
+                ["o", "os_parsers"],
+                UAOSRegexRaw::new,
+            )?,
+        })
+    }
+
+    /// Applies the changes `resources.py` makes to the rules of
+    /// `regexes.yaml`.
+    fn patch(self) -> Self {
+        UARegexesRaw {
+            browser_parsers: self
+                .browser_parsers
+                .into_iter()
+                .map(|r| r.patch())
+                .collect(),
+            device_parsers: self.device_parsers.into_iter().map(|r| r.patch()).collect(),
+            os_parsers: self.os_parsers.into_iter().map(|r| r.patch()).collect(),
+        }
+    }
+
+    fn extend(&mut self, other: UARegexesRaw) {
+        self.browser_parsers.extend(other.browser_parsers);
+        self.device_parsers.extend(other.device_parsers);
+        self.os_parsers.extend(other.os_parsers);
+    }
+
+    fn compile(self, options: &LoadOptions) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let mut diagnostics = Vec::new();
+        let regexes = self.compile_layer(Layer::Base, options, &mut diagnostics)?;
+        Ok((RegexDatabase::new(regexes), diagnostics))
+    }
+
+    fn compile_layer(
+        self,
+        layer: Layer,
+        options: &LoadOptions,
+        diagnostics: &mut Vec<RuleError>,
+    ) -> Result<UARegexes, Error> {
+        Ok(UARegexes {
+            browser_parsers: Rules::compile(
+                Category::Browser,
+                layer,
+                self.browser_parsers,
+                options,
+                diagnostics,
+            )?,
+            os_parsers: Rules::compile(Category::OS, layer, self.os_parsers, options, diagnostics)?,
+            device_parsers: Rules::compile(
+                Category::Device,
+                layer,
+                self.device_parsers,
+                options,
+                diagnostics,
+            )?,
+        })
+    }
+}
+
+fn raw_rules<R>(
+    file: &mut RawFile,
+    category: Category,
+    names: [&str; 2],
+    new: fn(RawFields) -> Option<R>,
+) -> Result<Vec<R>, Error> {
+    let rules = take(file, &names).unwrap_or_default();
+    rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, fields)| {
+            new(fields)
+                .ok_or_else(|| Error::Invalid(format!("{} rule {} has no regex", category, index)))
+        })
+        .collect()
+}
+
+/// Removes the value of the first of `names` present in `map`.
+fn take<V>(map: &mut BTreeMap<String, V>, names: &[&str]) -> Option<V> {
+    names.iter().find_map(|name| map.remove(*name))
+}
+
+/// Options for loading a `RegexDatabase`.
+///
+/// By default loading fails on the first rule whose regular expression
+/// cannot be compiled. In lenient mode such rules are skipped and returned
+/// as diagnostics along with the database.
+///
+/// ```rust
+/// use uap_rust::{Category, LoadOptions};
+/// let yaml = r#"
+/// user_agent_parsers:
+///   - regex: '(Foo'
+///   - regex: '(Bar)/(\d+)'
+/// os_parsers: []
+/// device_parsers: []
+/// "#;
+/// let (db, diagnostics) = LoadOptions::new().lenient(true).from_yaml(yaml).unwrap();
+/// assert_eq!(diagnostics[0].category, Category::Browser);
+/// assert_eq!(diagnostics[0].index, 0);
+/// assert_eq!(db.browser("Bar/2").family, "Bar");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    lenient: bool,
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        LoadOptions::default()
+    }
+
+    /// Skips rules with an invalid regular expression instead of failing.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Loads a database from the MessagePack encoded rules in `data`.
+    pub fn from_slice(&self, data: &[u8]) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let raw = UARegexesRaw::new(rmps::from_slice(data)?)?;
+        raw.compile(self)
+    }
+
+    /// Loads a database from MessagePack encoded rules read from `reader`.
+    pub fn from_reader<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let raw = UARegexesRaw::new(rmps::from_read(reader)?)?;
+        raw.compile(self)
+    }
+
+    /// Loads a database from a file with MessagePack encoded rules.
+    pub fn from_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let file = File::open(path)?;
+        self.from_reader(BufReader::new(file))
+    }
+
+    /// Loads a database from the contents of uap-core's `regexes.yaml`.
+    pub fn from_yaml(&self, yaml: &str) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let raw = UARegexesRaw::new(serde_yaml::from_str(yaml)?)?;
+        raw.patch().compile(self)
+    }
+
+    /// Loads a database from uap-core's `regexes.yaml` read from `reader`.
+    pub fn from_yaml_reader<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let raw = UARegexesRaw::new(serde_yaml::from_reader(reader)?)?;
+        raw.patch().compile(self)
+    }
+
+    /// Loads a database from a `regexes.yaml` file of uap-core.
+    pub fn from_yaml_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(RegexDatabase, Vec<RuleError>), Error> {
+        let file = File::open(path)?;
+        self.from_yaml_reader(BufReader::new(file))
+    }
+}
+
+/// Builds a `RegexDatabase` from a base database and custom rules.
+///
+/// The custom rules use the layout of uap-core's `regexes.yaml`, lists not
+/// needed can be left out. For each category the rules are tried in this
+/// order, the first matching rule wins:
+///
+/// 1. the override rules, in the order they were added,
+/// 2. the rules of the base database, except the disabled ones,
+/// 3. the fallback rules, in the order they were added.
+///
+/// Rules of the base database are disabled by their index, as reported by
+/// `RegexDatabase::explain`, or by their pattern. Both refer to rules of the
+/// base database's `Layer::Base`. Disabled rules not found in the base
+/// database are ignored.
+///
+/// ```rust
+/// use uap_rust::{Category, DatabaseBuilder};
+/// let db = DatabaseBuilder::new()
+///     .overrides_yaml(r#"
+/// user_agent_parsers:
+///   - regex: '(AcmeApp)/(\d+)\.(\d+)'
+/// device_parsers:
+///   - regex: '; (AcmePad) (\d+)'
+///     brand_replacement: 'Acme'
+///     model_replacement: '$1 $2'
+/// "#)
+///     .disable(Category::Browser, 0)
+///     .build()
+///     .unwrap();
+/// let agent = "Mozilla/5.0 (Linux; Android 12; AcmePad 2) AcmeApp/3.1";
+/// assert_eq!(db.browser(agent).family, "AcmeApp");
+/// assert_eq!(db.device(agent).brand.as_ref().unwrap(), "Acme");
+/// ```
+#[derive(Debug, Clone)]
+pub struct DatabaseBuilder {
+    base: Arc<RegexDatabase>,
+    overrides: Vec<String>,
+    fallbacks: Vec<String>,
+    disabled: Vec<(Category, Disabled)>,
+}
+
+#[derive(Debug, Clone)]
+enum Disabled {
+    Index(usize),
+    Pattern(String),
+}
+
+impl DatabaseBuilder {
+    /// Starts from the built-in database.
+    pub fn new() -> Self {
+        DatabaseBuilder::with_base(RegexDatabase::builtin())
+    }
+
+    /// Starts from the database `base`.
+    pub fn with_base(base: Arc<RegexDatabase>) -> Self {
+        DatabaseBuilder {
+            base,
+            overrides: Vec::new(),
+            fallbacks: Vec::new(),
+            disabled: Vec::new(),
+        }
+    }
+
+    /// Adds rules tried before the rules of the base database.
+    pub fn overrides_yaml(mut self, yaml: &str) -> Self {
+        self.overrides.push(yaml.into());
+        self
+    }
+
+    /// Adds rules tried after the rules of the base database.
+    pub fn fallbacks_yaml(mut self, yaml: &str) -> Self {
+        self.fallbacks.push(yaml.into());
+        self
+    }
+
+    /// Disables the rule at `index` of `category` in the base database.
+    pub fn disable(mut self, category: Category, index: usize) -> Self {
+        self.disabled.push((category, Disabled::Index(index)));
+        self
+    }
+
+    /// Disables the rules of `category` in the base database with the
+    /// regular expression `pattern`. The pattern may be given as in
+    /// `regexes.yaml` or as reported by `RegexDatabase::explain`.
+    pub fn disable_pattern(mut self, category: Category, pattern: &str) -> Self {
+        self.disabled
+            .push((category, Disabled::Pattern(pattern.into())));
+        self
+    }
+
+    /// Compiles the custom rules and builds the database. Fails if the
+    /// custom rules are malformed or contain an invalid regular expression.
+    pub fn build(self) -> Result<RegexDatabase, Error> {
+        let options = LoadOptions::new();
+        let overrides = compile_yaml(&self.overrides, Layer::Override, &options)?;
+        let fallbacks = compile_yaml(&self.fallbacks, Layer::Fallback, &options)?;
+        // Compare patterns before and after patching
+        let disabled: Vec<(Category, Disabled)> = self
+            .disabled
+            .into_iter()
+            .flat_map(|(category, disabled)| match disabled {
+                Disabled::Pattern(pattern) => {
+                    let patched = Disabled::Pattern(patch_regex(&pattern));
+                    vec![(category, Disabled::Pattern(pattern)), (category, patched)]
+                }
+                index => vec![(category, index)],
+            })
+            .collect();
+        let keep = |category: Category, (layer, index): (Layer, usize), pattern: &str| {
+            layer != Layer::Base
+                || !disabled.iter().any(|(c, disabled)| {
+                    *c == category
+                        && match disabled {
+                            Disabled::Index(i) => *i == index,
+                            Disabled::Pattern(p) => p == pattern,
+                        }
+                })
+        };
+        let base = &self.base.regexes;
+        Ok(RegexDatabase::new(UARegexes {
+            browser_parsers: Rules::layered(
+                overrides.browser_parsers,
+                &base.browser_parsers,
+                |origin, rule| keep(Category::Browser, origin, rule.regex().as_str()),
+                fallbacks.browser_parsers,
+            ),
+            os_parsers: Rules::layered(
+                overrides.os_parsers,
+                &base.os_parsers,
+                |origin, rule| keep(Category::OS, origin, rule.regex().as_str()),
+                fallbacks.os_parsers,
+            ),
+            device_parsers: Rules::layered(
+                overrides.device_parsers,
+                &base.device_parsers,
+                |origin, rule| keep(Category::Device, origin, rule.regex().as_str()),
+                fallbacks.device_parsers,
+            ),
+        }))
+    }
+}
+
+impl Default for DatabaseBuilder {
+    fn default() -> Self {
+        DatabaseBuilder::new()
+    }
+}
+
+/// Compiles the rules of all `documents` as one layer.
+fn compile_yaml(
+    documents: &[String],
+    layer: Layer,
+    options: &LoadOptions,
+) -> Result<UARegexes, Error> {
+    let mut raw = UARegexesRaw::default();
+    for yaml in documents {
+        raw.extend(UARegexesRaw::new(serde_yaml::from_str(yaml)?)?);
+    }
+    raw.patch().compile_layer(layer, options, &mut Vec::new())
 }
 
-#[derive(Debug, Deserialize)]
-pub(super) struct UARegexes<'a> {
-    #[serde(borrow, rename = "b")]
-    browser_parsers: Vec<UABrowserRegex<'a>>,
-    #[serde(borrow, rename = "d")]
-    device_parsers: Vec<UADeviceRegex<'a>>,
-    #[serde(borrow, rename = "o")]
-    os_parsers: Vec<UAOSRegex<'a>>,
+/// Pairs the rules of a category with their prebuilt DFAs. Rules without
+/// DFA, e.g. if the rules changed since they were built, are compiled.
+#[cfg(feature = "prebuilt")]
+fn with_dfas<R>(rules: Vec<R>, dfas: Vec<Option<Dfa>>) -> Vec<Prebuilt<R>> {
+    let dfas = dfas.into_iter().chain(std::iter::repeat_with(|| None));
+    rules
+        .into_iter()
+        .zip(dfas)
+        .map(|(raw, dfa)| Prebuilt { raw, dfa })
+        .collect()
+}
+
+/// A set of rules for detecting the browser, the device and the operating
+/// system of a user agent.
+///
+/// The database bundled with the crate is available through
+/// `RegexDatabase::builtin()` and is used by `BorrowingParser::new` and
+/// `OwningParser::new`. A database with a different rule set can be loaded
+/// from the MessagePack format generated by `resources.py` and handed to the
+/// parsers with `BorrowingParser::with_database` or
+/// `OwningParser::with_database`.
+#[derive(Debug)]
+pub struct RegexDatabase {
+    regexes: UARegexes,
+    /// The Spider rules of `device_parsers`, built on first use.
+    bots: OnceCell<BotMatcher>,
+}
+
+impl RegexDatabase {
+    fn new(regexes: UARegexes) -> RegexDatabase {
+        RegexDatabase {
+            regexes,
+            bots: OnceCell::INIT,
+        }
+    }
+
+    /// Returns the database compiled from the rules bundled with the crate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bundled rules cannot be compiled, see `load`.
+    pub fn builtin() -> Arc<RegexDatabase> {
+        UA_PARSER_REGEXES.clone()
+    }
+
+    /// Returns the database compiled from the rules bundled with the crate,
+    /// or an error if they cannot be compiled.
+    ///
+    /// The database is compiled once, on the first successful call to
+    /// `load`, `builtin` or one of the parsers using it.
+    pub fn load() -> Result<Arc<RegexDatabase>, Error> {
+        if let Some(db) = BUILTIN.get() {
+            return Ok(db.clone());
+        }
+        let db = Arc::new(RegexDatabase::compile_builtin()?);
+        // Keep the first database if another thread was faster
+        let _ = BUILTIN.set(db);
+        Ok(BUILTIN.get().expect("database was set").clone())
+    }
+
+    #[cfg(not(feature = "prebuilt"))]
+    fn compile_builtin() -> Result<RegexDatabase, Error> {
+        RegexDatabase::from_slice(UA_PARSER_REGEX_DATA)
+    }
+
+    /// Uses the DFAs built by `build.rs` instead of compiling the rules.
+    #[cfg(feature = "prebuilt")]
+    fn compile_builtin() -> Result<RegexDatabase, Error> {
+        let raw = UARegexesRaw::new(rmps::from_slice(UA_PARSER_REGEX_DATA)?)?;
+        let [browser, os, device] = match matcher::prebuilt() {
+            Some(dfas) => dfas,
+            None => return raw.compile(&LoadOptions::new()).map(|(db, _)| db),
+        };
+        let options = LoadOptions::new();
+        let mut diagnostics = Vec::new();
+        let regexes = UARegexes {
+            browser_parsers: Rules::compile(
+                Category::Browser,
+                Layer::Base,
+                with_dfas(raw.browser_parsers, browser),
+                &options,
+                &mut diagnostics,
+            )?,
+            os_parsers: Rules::compile(
+                Category::OS,
+                Layer::Base,
+                with_dfas(raw.os_parsers, os),
+                &options,
+                &mut diagnostics,
+            )?,
+            device_parsers: Rules::compile(
+                Category::Device,
+                Layer::Base,
+                with_dfas(raw.device_parsers, device),
+                &options,
+                &mut diagnostics,
+            )?,
+        };
+        Ok(RegexDatabase::new(regexes))
+    }
+
+    /// Loads a database from the MessagePack encoded rules in `data`.
+    pub fn from_slice(data: &[u8]) -> Result<RegexDatabase, Error> {
+        LoadOptions::new().from_slice(data).map(|(db, _)| db)
+    }
+
+    /// Loads a database from MessagePack encoded rules read from `reader`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<RegexDatabase, Error> {
+        LoadOptions::new().from_reader(reader).map(|(db, _)| db)
+    }
+
+    /// Loads a database from a file with MessagePack encoded rules.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<RegexDatabase, Error> {
+        LoadOptions::new().from_file(path).map(|(db, _)| db)
+    }
+
+    /// Loads a database from the contents of uap-core's `regexes.yaml`.
+    ///
+    /// The rules are patched the same way `resources.py` does it before
+    /// they are compiled.
+    pub fn from_yaml(yaml: &str) -> Result<RegexDatabase, Error> {
+        LoadOptions::new().from_yaml(yaml).map(|(db, _)| db)
+    }
+
+    /// Loads a database from uap-core's `regexes.yaml` read from `reader`.
+    pub fn from_yaml_reader<R: Read>(reader: R) -> Result<RegexDatabase, Error> {
+        LoadOptions::new()
+            .from_yaml_reader(reader)
+            .map(|(db, _)| db)
+    }
+
+    /// Loads a database from a `regexes.yaml` file of uap-core.
+    pub fn from_yaml_file<P: AsRef<Path>>(path: P) -> Result<RegexDatabase, Error> {
+        LoadOptions::new().from_yaml_file(path).map(|(db, _)| db)
+    }
+
+    /// Parses the browser information of `agent` using this database.
+    pub fn browser<'a>(&'a self, agent: &'a str) -> Browser<'a> {
+        self.regexes
+            .browser_parsers
+            .find_map(agent, |b| b.parse(agent))
+            .unwrap_or_default()
+    }
+
+    /// Parses the operating system information of `agent` using this database.
+    pub fn os<'a>(&'a self, agent: &'a str) -> OS<'a> {
+        self.regexes
+            .os_parsers
+            .find_map(agent, |o| o.parse(agent))
+            .unwrap_or_default()
+    }
+
+    /// Parses the device information of `agent` using this database.
+    pub fn device<'a>(&'a self, agent: &'a str) -> Device<'a> {
+        self.regexes
+            .device_parsers
+            .find_map(agent, |d| d.parse(agent))
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if `agent` is a bot, i.e. its device is "Spider".
+    ///
+    /// Only the device rules which may produce "Spider" are tried,
+    /// combined into a single regular expression. This is considerably
+    /// faster than `device` for the large majority of user agents.
+    pub fn is_bot(&self, agent: &str) -> bool {
+        match self.check_bot(agent) {
+            Verdict::Human => false,
+            Verdict::Bot(_) => true,
+            Verdict::Unsure => self.device(agent).family == "Spider",
+        }
+    }
+
+    /// Like `is_bot`, but returns the device if it was determined on the
+    /// way.
+    pub(crate) fn check_bot<'a>(&'a self, agent: &'a str) -> Verdict<Device<'a>> {
+        let rules = &self.regexes.device_parsers.rules;
+        let is_spider = |i: usize| rules[i].parse(agent).is_some_and(|d| d.family == "Spider");
+        match self.bots().check(agent, is_spider) {
+            Verdict::Human => Verdict::Human,
+            Verdict::Bot(i) => match rules[i].parse(agent) {
+                Some(device) => Verdict::Bot(device),
+                None => Verdict::Unsure,
+            },
+            Verdict::Unsure => Verdict::Unsure,
+        }
+    }
+
+    fn bots(&self) -> &BotMatcher {
+        self.bots.get_or_init(|| {
+            BotMatcher::new(self.regexes.device_parsers.rules.iter().map(|rule| {
+                let spider =
+                    SpiderRule::of(rule.regex.as_str(), rule.device_replacement.as_deref());
+                (rule.regex.as_str(), spider)
+            }))
+        })
+    }
+
+    /// Compiles all rules now instead of on first use.
+    ///
+    /// Rules are only checked for syntax errors and their estimated size
+    /// when they are loaded and compiled once they are first needed, so
+    /// each category costs nothing until it is parsed. Services can call
+    /// `warm_up` on startup to avoid the delay on the first requests.
+    ///
+    /// Rules which might exceed the size limit of the `regex` crate are
+    /// compiled when loading already and fail there. Returns the rules
+    /// which exceed it nevertheless, these never match.
+    pub fn warm_up(&self) -> Vec<RuleError> {
+        let mut diagnostics = Vec::new();
+        let regexes = &self.regexes;
+        regexes
+            .browser_parsers
+            .warm_up(Category::Browser, &mut diagnostics);
+        regexes.os_parsers.warm_up(Category::OS, &mut diagnostics);
+        regexes
+            .device_parsers
+            .warm_up(Category::Device, &mut diagnostics);
+        self.bots();
+        diagnostics
+    }
+
+    /// Returns the number of rules of `category`.
+    pub fn rule_count(&self, category: Category) -> usize {
+        match category {
+            Category::Browser => self.regexes.browser_parsers.rules.len(),
+            Category::OS => self.regexes.os_parsers.rules.len(),
+            Category::Device => self.regexes.device_parsers.rules.len(),
+        }
+    }
+
+    /// Parses a batch of user agents using this database, see `parse_all`.
+    pub fn parse_all<'a, I, S>(&'a self, agents: I) -> Batch<'a>
+    where
+        I: IntoIterator<Item = &'a S>,
+        S: AsRef<str> + ?Sized + 'a,
+    {
+        Batch::parse(agents, self)
+    }
+
+    /// Reports which rules determine the browser, operating system and
+    /// device of `agent`, see `Explanation`.
+    pub fn explain<'a>(&'a self, agent: &'a str) -> Explanation<'a> {
+        let regexes = &self.regexes;
+        Explanation {
+            user_agent: agent,
+            browser: regexes.browser_parsers.explain(
+                Category::Browser,
+                agent,
+                UABrowserRegex::value,
+            ),
+            os: regexes
+                .os_parsers
+                .explain(Category::OS, agent, UAOSRegex::value),
+            device: regexes
+                .device_parsers
+                .explain(Category::Device, agent, UADeviceRegex::value),
+        }
+    }
 }
 
 macro_rules! derive_with_regex_field {
@@ -35,104 +927,148 @@ macro_rules! derive_with_regex_field {
     	struct $name:ident $name_raw:ident {
     		$(
     			$(#[$field_meta:meta])*
-    			$field:ident : $field_type:ty
+    			$field:ident : $field_type:ty = $names:expr
     		),*
     	}
     ) => {
     	$(#[$meta])*
-    	struct $name<'a> {
-    		regex: Regex,
+    	struct $name {
+    		regex: Matcher,
     		$($field: $field_type),*
     	}
 
 
-    	impl<'a> PartialEq for $name<'a> {
+    	impl PartialEq for $name {
     		fn eq(&self, other: &$name) -> bool {
     			$(self.$field == other.$field && )*
     			self.regex.as_str() == other.regex.as_str()
     		}
     	}
 
-    	impl<'a> Eq for $name<'a> { }
+    	impl Eq for $name { }
+
+    	impl Rule for $name {
+    		fn regex(&self) -> &Matcher {
+    			&self.regex
+    		}
+
+    		fn templates(&self) -> Vec<(&'static str, &str)> {
+    			let mut templates = Vec::new();
+    			$(
+    			    if let Some(template) = &self.$field {
+    			        templates.push((stringify!($field), &template[..]));
+    			    }
+    			)*
+    			templates
+    		}
+    	}
 
     	$(#[$meta])*
-    	#[derive(Deserialize)]
     	// Why not call this Raw and use macro hygene?
-	    struct $name_raw<'a> {
-	    	#[serde(borrow, rename="r")]
-	    	regex: &'a str,
+	    struct $name_raw {
+	    	regex: String,
+	    	regex_flag: Option<String>,
 	    	$(
 	    		$(#[$field_meta])*
 	    		$field: $field_type
 	    	),*
 	    }
 
-    	impl<'de: 'a, 'a> Deserialize<'de> for $name<'a> {
-    	    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    	        where D: Deserializer<'de>
-    	    {
-    	        let raw = $name_raw::deserialize(deserializer)?;
-                let mut builder = RegexBuilder::new(&raw.regex);
-    	        let regex = match builder.size_limit(2 * 10_485_760).build() {
-    	        	Ok(regex) => regex,
-    	        	Err(err) => {
-    	        		let err = D::Error::custom(
-    	        			format!("Error compiling regex pattern.\n  pattern: {}\n  error: {}",
-    	        				    raw.regex, err));
-    	        		return Err(err);
-    	        	}
-    	        };
+    	impl $name_raw {
+    	    /// Takes the fields of a rule, `None` if it has no regex.
+    	    fn new(mut fields: RawFields) -> Option<$name_raw> {
+    	        Some($name_raw {
+    	        	regex: take(&mut fields, &["r", "regex"])??,
+    	        	regex_flag: take(&mut fields, &["regex_flag"]).flatten(),
+    	        	$($field: take(&mut fields, &$names).flatten()),*
+    	        })
+    	    }
+
+    	    /// Applies the changes `resources.py` makes to the rules from
+    	    /// uap-core's `regexes.yaml`.
+    	    fn patch(mut self) -> Self {
+    	        self.regex = patch_regex(&self.regex);
+    	        if let Some(flag) = self.regex_flag.take() {
+    	            self.regex = format!("(?{}){}", flag, self.regex);
+    	        }
+    	        self
+    	    }
+
+    	}
+
+    	impl RawRule for $name_raw {
+    	    type Rule = $name;
+
+
+    	    fn compile(self) -> Result<$name, (String, regex::Error)> {
     	        Ok($name {
-    	        	regex: regex,
-    	        	$($field: raw.$field),*
+    	        	regex: Matcher::new(self.regex)?,
+    	        	$($field: self.$field),*
     	        })
     	    }
+
+    	    #[cfg(feature = "prebuilt")]
+    	    fn prebuilt(self, dfa: Dfa) -> $name {
+    	        $name {
+    	        	regex: Matcher::prebuilt(self.regex, dfa),
+    	        	$($field: self.$field),*
+    	        }
+    	    }
     	}
     }
 }
 
 derive_with_regex_field! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     struct UABrowserRegex UABrowserRegexRaw {
-        #[serde(borrow, default, rename="f")]
-        family_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="1")]
-        v1_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="2")]
-        v2_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="3")]
-        v3_replacement: Option<&'a str>
+        family_replacement: Option<String> = ["f", "family_replacement"],
+        v1_replacement: Option<String> = ["1", "v1_replacement"],
+        v2_replacement: Option<String> = ["2", "v2_replacement"],
+        v3_replacement: Option<String> = ["3", "v3_replacement"]
     }
 }
 
 derive_with_regex_field! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     struct UAOSRegex UAOSRegexRaw {
-        #[serde(borrow, default, rename="o")]
-        os_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="1")]
-        os_v1_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="2")]
-        os_v2_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="3")]
-        os_v3_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="4")]
-        os_v4_replacement: Option<&'a str>
+        os_replacement: Option<String> = ["o", "os_replacement"],
+        os_v1_replacement: Option<String> = ["1", "os_v1_replacement"],
+        os_v2_replacement: Option<String> = ["2", "os_v2_replacement"],
+        os_v3_replacement: Option<String> = ["3", "os_v3_replacement"],
+        os_v4_replacement: Option<String> = ["4", "os_v4_replacement"]
     }
 }
 
 derive_with_regex_field! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     struct UADeviceRegex UADeviceRegexRaw {
-        #[serde(borrow, default, rename="d")]
-        device_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="b")]
-        brand_replacement: Option<&'a str>,
-        #[serde(borrow, default, rename="m")]
-        model_replacement: Option<&'a str>
+        device_replacement: Option<String> = ["d", "device_replacement"],
+        brand_replacement: Option<String> = ["b", "brand_replacement"],
+        model_replacement: Option<String> = ["m", "model_replacement"]
     }
 }
 
+/// Rewrites a pattern of uap-core into the syntax accepted by the `regex`
+/// crate, like `resources.py` does.
+fn patch_regex(regex: &str) -> String {
+    // Fix. See https://github.com/ua-parser/uap-core/pull/310
+    let regex = regex.replace("|)", ")?");
+    // Remove escapes of characters which need no escaping
+    let mut patched = String::with_capacity(regex.len());
+    let mut previous = None;
+    let mut chars = regex.chars().peekable();
+    while let Some(c) = chars.next() {
+        let needless_escape = c == '\\'
+            && previous != Some('\\')
+            && matches!(chars.peek(), Some(' ') | Some('/') | Some('!'));
+        if !needless_escape {
+            patched.push(c);
+        }
+        previous = Some(c);
+    }
+    patched
+}
+
 fn replace_matches<'a>(s: &'a str, caps: &Captures<'a>) -> Option<Cow<'a, str>> {
     let s: Cow<str> = if s.as_bytes().contains(&b'$') {
         let mut dst = String::with_capacity(2 * s.len());
@@ -183,131 +1119,136 @@ fn get_or_none<'a>(c: &Captures<'a>, i: usize) -> Option<Cow<'a, str>> {
     }
 }
 
-impl<'a: 'b, 'b> UABrowserRegex<'a> {
-    fn parse(&self, agent: &'b str) -> Option<Browser<'b>> {
-        self.regex.captures(agent).map(|c| {
-            let family = self
-                .family_replacement
-                .and_then(|f| {
-                    if let Some(group1) = c.get(1) {
-                        Some(Cow::Owned(f.replace("$1", group1.as_str())))
-                    } else {
-                        Some(Cow::Borrowed(f))
-                    }
-                })
-                .or_else(|| c.get(1).map(|c| Cow::Borrowed(c.as_str())))
-                .unwrap_or_else(|| Cow::Borrowed(DEFAULT_NAME));
-
-            let major = self
-                .v1_replacement
-                .map(|v1| Cow::Borrowed(v1))
-                .or_else(|| get_or_none(&c, 2));
-            let minor = self
-                .v2_replacement
-                .map(|v2| Cow::Borrowed(v2))
-                .or_else(|| get_or_none(&c, 3));
-            let patch = self
-                .v3_replacement
-                .map(|v2| Cow::Borrowed(v2))
-                .or_else(|| get_or_none(&c, 4));
-
-            Browser {
-                family,
-                major,
-                minor,
-                patch,
-            }
-        })
+impl UABrowserRegex {
+    fn parse<'a>(&'a self, agent: &'a str) -> Option<Browser<'a>> {
+        self.regex.captures(agent).map(|c| self.value(&c))
+    }
+
+    fn value<'a>(&'a self, c: &Captures<'a>) -> Browser<'a> {
+        let family = self
+            .family_replacement
+            .as_deref()
+            .map(|f| {
+                if let Some(group1) = c.get(1) {
+                    Cow::Owned(f.replace("$1", group1.as_str()))
+                } else {
+                    Cow::Borrowed(f)
+                }
+            })
+            .or_else(|| c.get(1).map(|c| Cow::Borrowed(c.as_str())))
+            .unwrap_or(Cow::Borrowed(DEFAULT_NAME));
+
+        let major = self
+            .v1_replacement
+            .as_deref()
+            .map(Cow::Borrowed)
+            .or_else(|| get_or_none(c, 2));
+        let minor = self
+            .v2_replacement
+            .as_deref()
+            .map(Cow::Borrowed)
+            .or_else(|| get_or_none(c, 3));
+        let patch = self
+            .v3_replacement
+            .as_deref()
+            .map(Cow::Borrowed)
+            .or_else(|| get_or_none(c, 4));
+
+        Browser {
+            family,
+            major,
+            minor,
+            patch,
+        }
     }
 }
 
-impl<'a: 'b, 'b> UAOSRegex<'a> {
-    fn parse(&self, agent: &'b str) -> Option<OS<'b>> {
-        self.regex.captures(agent).map(|c| {
-            let family: Cow<str> = self
-                .os_replacement
-                .map_or_else(|| get_or_none(&c, 1), |f| replace_matches(f, &c))
-                .unwrap_or_else(|| Cow::Borrowed(DEFAULT_NAME));
-            let major = self
-                .os_v1_replacement
-                .map_or_else(|| get_or_none(&c, 2), |m| replace_matches(m, &c));
-            let minor = self
-                .os_v2_replacement
-                .map_or_else(|| get_or_none(&c, 3), |m| replace_matches(m, &c));
-            let patch = self
-                .os_v3_replacement
-                .map_or_else(|| get_or_none(&c, 4), |p| replace_matches(p, &c));
-            let patch_minor = self
-                .os_v4_replacement
-                .map_or_else(|| get_or_none(&c, 5), |p| replace_matches(p, &c));
-
-            OS {
-                family,
-                major,
-                minor,
-                patch,
-                patch_minor,
-            }
-        })
+impl UAOSRegex {
+    fn parse<'a>(&'a self, agent: &'a str) -> Option<OS<'a>> {
+        self.regex.captures(agent).map(|c| self.value(&c))
+    }
+
+    fn value<'a>(&'a self, c: &Captures<'a>) -> OS<'a> {
+        let family: Cow<str> = self
+            .os_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 1), |f| replace_matches(f, c))
+            .unwrap_or(Cow::Borrowed(DEFAULT_NAME));
+        let major = self
+            .os_v1_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 2), |m| replace_matches(m, c));
+        let minor = self
+            .os_v2_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 3), |m| replace_matches(m, c));
+        let patch = self
+            .os_v3_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 4), |p| replace_matches(p, c));
+        let patch_minor = self
+            .os_v4_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 5), |p| replace_matches(p, c));
+
+        OS {
+            family,
+            major,
+            minor,
+            patch,
+            patch_minor,
+        }
     }
 }
 
-impl<'a: 'b, 'b> UADeviceRegex<'a> {
-    fn parse(&self, agent: &'b str) -> Option<Device<'b>> {
-        self.regex.captures(agent).map(|c| {
-            let family = self
-                .device_replacement
-                .map_or_else(|| get_or_none(&c, 1), |f| replace_matches(f, &c))
-                .unwrap_or_else(|| Cow::Borrowed(DEFAULT_NAME));
-            let brand = self.brand_replacement.and_then(|m| replace_matches(m, &c));
-            let model = self
-                .model_replacement
-                .map_or_else(|| get_or_none(&c, 1), |m| replace_matches(m, &c));
-            Device {
-                family,
-                brand,
-                model,
-            }
-        })
+impl UADeviceRegex {
+    fn parse<'a>(&'a self, agent: &'a str) -> Option<Device<'a>> {
+        self.regex.captures(agent).map(|c| self.value(&c))
+    }
+
+    fn value<'a>(&'a self, c: &Captures<'a>) -> Device<'a> {
+        let family = self
+            .device_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 1), |f| replace_matches(f, c))
+            .unwrap_or(Cow::Borrowed(DEFAULT_NAME));
+        let brand = self
+            .brand_replacement
+            .as_deref()
+            .and_then(|m| replace_matches(m, c));
+        let model = self
+            .model_replacement
+            .as_deref()
+            .map_or_else(|| get_or_none(c, 1), |m| replace_matches(m, c));
+        Device {
+            family,
+            brand,
+            model,
+        }
     }
 }
 
 impl<'a> From<&'a str> for Browser<'a> {
     fn from(agent: &'a str) -> Self {
-        UA_PARSER_REGEXES
-            .browser_parsers
-            .iter()
-            .filter_map(|b| b.parse(agent))
-            .next()
-            .unwrap_or_else(Browser::default)
+        UA_PARSER_REGEXES.browser(agent)
     }
 }
 
 impl<'a> From<&'a str> for OS<'a> {
     fn from(agent: &'a str) -> Self {
-        UA_PARSER_REGEXES
-            .os_parsers
-            .iter()
-            .filter_map(|o| o.parse(agent))
-            .next()
-            .unwrap_or_else(Self::default)
+        UA_PARSER_REGEXES.os(agent)
     }
 }
 
 impl<'a> From<&'a str> for Device<'a> {
     fn from(agent: &'a str) -> Self {
-        UA_PARSER_REGEXES
-            .device_parsers
-            .iter()
-            .filter_map(|d| d.parse(agent))
-            .next()
-            .unwrap_or_else(Device::default)
+        UA_PARSER_REGEXES.device(agent)
     }
 }
 
 #[test]
 fn test_replace_matches() {
-    let re = Regex::new(r"Ok (\d+) (\d+)").unwrap();
+    let re = regex::Regex::new(r"Ok (\d+) (\d+)").unwrap();
     let captures = re.captures("Ok 1 2").unwrap();
     assert_eq!(
         replace_matches("$2 $1 $2", &captures),
@@ -315,12 +1256,214 @@ fn test_replace_matches() {
     );
 }
 
+#[test]
+fn test_custom_database() {
+    use std::collections::BTreeMap;
+
+    let rule: BTreeMap<_, _> = vec![("r", "(Foo)Bar/(\\d+)"), ("f", "$1 App")]
+        .into_iter()
+        .collect();
+    let mut rules = BTreeMap::new();
+    rules.insert("b", vec![rule]);
+    rules.insert("d", vec![]);
+    rules.insert("o", vec![]);
+    let db = RegexDatabase::from_slice(&rmps::to_vec(&rules).unwrap()).unwrap();
+
+    let browser = db.browser("Mozilla/5.0 FooBar/12");
+    assert_eq!(browser.family, "Foo App");
+    assert_eq!(browser.major.as_ref().unwrap(), "12");
+    assert_eq!(db.browser("Firefox/60.0"), Browser::default());
+    assert_eq!(db.os("Firefox/60.0"), OS::default());
+}
+
+#[test]
+fn test_is_bot_captured_spider() {
+    let yaml = r#"
+user_agent_parsers: []
+os_parsers: []
+device_parsers:
+  - regex: '(Phone)/1'
+  - regex: '(\w+)Crawler'
+    device_replacement: '$1'
+  - regex: 'Googlebot'
+    device_replacement: 'Spider'
+"#;
+    let db = RegexDatabase::from_yaml(yaml).unwrap();
+    for &(agent, is_bot) in [
+        ("SpiderCrawler/1.0", true),
+        ("FooCrawler/1.0 Googlebot", false),
+        ("Phone/1 SpiderCrawler", false),
+        ("Googlebot/2.1", true),
+        ("Firefox", false),
+    ]
+    .iter()
+    {
+        assert_eq!(db.is_bot(agent), is_bot, "{}", agent);
+        assert_eq!(db.device(agent).family == "Spider", is_bot, "{}", agent);
+    }
+}
+
+#[test]
+fn test_patch_regex() {
+    assert_eq!(patch_regex(r"(Foo|)Bar"), r"(Foo)?Bar");
+    assert_eq!(patch_regex(r"Foo\ Bar\/(\d+)\!"), r"Foo Bar/(\d+)!");
+    assert_eq!(patch_regex(r"Foo\\ Bar\.\\/"), r"Foo\\ Bar\.\\/");
+}
+
+#[test]
+fn test_yaml_database() {
+    let yaml = r#"
+user_agent_parsers:
+  - regex: '(Foo|)Bar\/(\d+)'
+    family_replacement: '$1 App'
+os_parsers:
+  - regex: '(fooOS) (\d+)'
+    regex_flag: 'i'
+    os_replacement: 'Foo OS'
+device_parsers:
+  - regex: '; (FooPhone)\ (\d+)'
+    brand_replacement: 'Foo'
+    model_replacement: '$1 $2'
+"#;
+    let db = RegexDatabase::from_yaml(yaml).unwrap();
+
+    let agent = "Mozilla/5.0 (FOOOS 12; FooPhone 3) FooBar/7";
+    let browser = db.browser(agent);
+    assert_eq!(browser.family, "Foo App");
+    assert_eq!(browser.major.as_ref().unwrap(), "7");
+    let os = db.os(agent);
+    assert_eq!(os.family, "Foo OS");
+    assert_eq!(os.major.as_ref().unwrap(), "12");
+    let device = db.device(agent);
+    assert_eq!(device.family, "FooPhone");
+    assert_eq!(device.brand.as_ref().unwrap(), "Foo");
+    assert_eq!(device.model.as_ref().unwrap(), "FooPhone 3");
+    assert_eq!(db.browser("Firefox/60.0").family, "Other");
+}
+
+#[test]
+fn test_invalid_rules() {
+    let yaml = r#"
+user_agent_parsers:
+  - regex: '(Foo)/(\d+)'
+os_parsers:
+  - regex: '(fooOS'
+  - regex: '(barOS) (\d+)'
+device_parsers:
+  - regex: '[Phone'
+"#;
+    match RegexDatabase::from_yaml(yaml) {
+        Err(Error::Rule(err)) => {
+            assert_eq!((err.category, err.index), (Category::OS, 0));
+            assert_eq!(err.pattern, "(fooOS");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let (db, diagnostics) = LoadOptions::new().lenient(true).from_yaml(yaml).unwrap();
+    let broken: Vec<_> = diagnostics.iter().map(|d| (d.category, d.index)).collect();
+    assert_eq!(broken, vec![(Category::OS, 0), (Category::Device, 0)]);
+    assert_eq!(db.os("barOS 2").family, "barOS");
+    assert_eq!(db.browser("Foo/1").family, "Foo");
+
+    let yaml = "user_agent_parsers:\n  - regex: 'Foo'\n  - family_replacement: 'Bar'\n";
+    match RegexDatabase::from_yaml(yaml) {
+        Err(Error::Invalid(msg)) => assert_eq!(msg, "browser rule 1 has no regex"),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_warm_up() {
+    let yaml = r#"
+user_agent_parsers:
+  - regex: '(Foo)/(\d+)'
+  - regex: '(Bar)/\w{100000}'
+os_parsers:
+  - regex: '(fooOS) (\d+)'
+device_parsers: []
+"#;
+    // Too large rules fail when loading, not only on first use
+    match RegexDatabase::from_yaml(yaml) {
+        Err(Error::Rule(err)) => {
+            assert_eq!((err.category, err.index), (Category::Browser, 1));
+            assert!(matches!(err.error, regex::Error::CompiledTooBig(_)));
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    let (db, diagnostics) = LoadOptions::new().lenient(true).from_yaml(yaml).unwrap();
+    let broken: Vec<_> = diagnostics.iter().map(|d| (d.category, d.index)).collect();
+    assert_eq!(broken, vec![(Category::Browser, 1)]);
+    assert!(db.warm_up().is_empty());
+    assert_eq!(db.browser("Foo/1").family, "Foo");
+    assert_eq!(db.os("fooOS 2").family, "fooOS");
+}
+
+#[test]
+fn test_database_builder() {
+    let base = Arc::new(
+        RegexDatabase::from_yaml(
+            r#"
+user_agent_parsers:
+  - regex: '(Foo)/(\d+)'
+  - regex: '(Bar)\/(\d+)'
+os_parsers: []
+device_parsers: []
+"#,
+        )
+        .unwrap(),
+    );
+    let db = DatabaseBuilder::with_base(base.clone())
+        .overrides_yaml("user_agent_parsers:\n  - regex: '(Foo)/(\\d+)'\n    family_replacement: 'Foo App'")
+        .fallbacks_yaml("user_agent_parsers:\n  - regex: '(Baz|Bar)/(\\d+)'\n    family_replacement: 'Other $1'")
+        .fallbacks_yaml("os_parsers:\n  - regex: '(BazOS)'")
+        .build()
+        .unwrap();
+    assert_eq!(db.browser("Foo/1").family, "Foo App");
+    assert_eq!(db.browser("Bar/1").family, "Bar");
+    assert_eq!(db.browser("Baz/1").family, "Other Baz");
+    assert_eq!(db.os("BazOS").family, "BazOS");
+    let explanation = db.explain("Baz/1");
+    let browser = explanation.browser.unwrap();
+    assert_eq!((browser.layer, browser.index), (Layer::Fallback, 0));
+
+    let by_index = DatabaseBuilder::with_base(base.clone())
+        .disable(Category::Browser, 1)
+        .build()
+        .unwrap();
+    let by_pattern = DatabaseBuilder::with_base(base)
+        .disable_pattern(Category::Browser, r"(Bar)\/(\d+)")
+        .disable_pattern(Category::OS, r"(Foo)/(\d+)")
+        .build()
+        .unwrap();
+    for db in &[by_index, by_pattern] {
+        assert_eq!(db.browser("Bar/1").family, "Other");
+        let browser = db.explain("Foo/1").browser.unwrap();
+        assert_eq!((browser.layer, browser.index), (Layer::Base, 0));
+    }
+
+    let broken = DatabaseBuilder::new()
+        .overrides_yaml("os_parsers:\n  - regex: '(Foo'")
+        .build();
+    match broken {
+        Err(Error::Rule(err)) => assert_eq!((err.category, err.index), (Category::OS, 0)),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_load_builtin() {
+    let db = RegexDatabase::load().unwrap();
+    assert!(Arc::ptr_eq(&db, &RegexDatabase::builtin()));
+}
+
 #[test]
 fn test_deserialize() {
     assert_eq!(
-        UA_PARSER_REGEXES.browser_parsers[0],
+        UA_PARSER_REGEXES.regexes.browser_parsers.rules[0],
         UABrowserRegex {
-            regex: Regex::new(r"(ESPN)[%20| ]+Radio/(\d+)\.(\d+)\.(\d+) CFNetwork").unwrap(),
+            regex: Matcher::new(r"(ESPN)[%20| ]+Radio/(\d+)\.(\d+)\.(\d+) CFNetwork".into())
+                .unwrap(),
             family_replacement: None,
             v1_replacement: None,
             v2_replacement: None,
@@ -0,0 +1,314 @@
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+#[cfg(feature = "serde")]
+use serde_derive::Serialize;
+
+use crate::{BotEntry, BotTable, UserAgentInformation};
+
+/// DNS lookups needed to verify bots, supplied by the caller.
+///
+/// The crate does not depend on a DNS library. Implementations typically
+/// wrap the resolver the application already uses, tests can use a
+/// `HashMap`.
+pub trait Resolver {
+    /// Returns the host names of `ip` from its PTR records. No records are
+    /// an empty list, not an error.
+    fn reverse(&self, ip: IpAddr) -> io::Result<Vec<String>>;
+
+    /// Returns the addresses of `host` from its A and AAAA records.
+    fn forward(&self, host: &str) -> io::Result<Vec<IpAddr>>;
+}
+
+impl<R: Resolver + ?Sized> Resolver for &R {
+    fn reverse(&self, ip: IpAddr) -> io::Result<Vec<String>> {
+        (**self).reverse(ip)
+    }
+
+    fn forward(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        (**self).forward(host)
+    }
+}
+
+/// Whether a request really comes from the bot named in its user agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Verification {
+    /// The address belongs to a host name of the bot's operator.
+    Verified,
+    /// The user agent claims to be a bot, but the address does not belong
+    /// to its operator.
+    Spoofed,
+    /// The bot is unknown, its operator publishes no host names or the
+    /// DNS lookups failed.
+    Unverifiable,
+}
+
+impl Verification {
+    /// Returns the name of the verdict in lower case, e.g. `"spoofed"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verification::Verified => "verified",
+            Verification::Spoofed => "spoofed",
+            Verification::Unverifiable => "unverifiable",
+        }
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Verifies bots with forward-confirmed reverse DNS.
+///
+/// The bot is identified by the user agent using a `BotTable`. Its client
+/// address is verified if a reverse lookup yields a host name in one of the
+/// bot's `hostnames` domains and a forward lookup of that host name yields
+/// the address again. Verdicts are cached per address and bot, except for
+/// failed lookups.
+///
+/// ```rust
+/// # use std::{io, net::IpAddr};
+/// use uap_rust::{BotVerifier, Resolver, Verification};
+///
+/// struct Dns;
+///
+/// impl Resolver for Dns {
+///     fn reverse(&self, ip: IpAddr) -> io::Result<Vec<String>> {
+///         Ok(vec!["crawl-66-249-66-1.googlebot.com".into()])
+///     }
+///
+///     fn forward(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+///         Ok(vec!["66.249.66.1".parse().unwrap()])
+///     }
+/// }
+///
+/// let verifier = BotVerifier::new(Dns);
+/// let agent = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+/// let verdict = verifier.verify_user_agent(agent, "66.249.66.1".parse().unwrap());
+/// assert_eq!(verdict, Verification::Verified);
+/// ```
+pub struct BotVerifier<R> {
+    resolver: R,
+    bots: BotTable,
+    max_entries: usize,
+    ttl: Duration,
+    cache: Mutex<Cache>,
+}
+
+/// Verdicts with the time they were determined, by address and bot name.
+type Cache = LruCache<(IpAddr, String), (Verification, Instant)>;
+
+impl<R: Resolver> BotVerifier<R> {
+    /// Creates a verifier for the bots of the built-in `BotTable`, caching
+    /// up to 10,000 verdicts for a day.
+    pub fn new(resolver: R) -> Self {
+        BotVerifier {
+            resolver,
+            bots: BotTable::builtin(),
+            max_entries: 10_000,
+            ttl: Duration::from_secs(24 * 60 * 60),
+            cache: Mutex::new(LruCache::unbounded()),
+        }
+    }
+
+    /// Uses `bots` to identify bots and their host names.
+    pub fn bots(mut self, bots: BotTable) -> Self {
+        self.bots = bots;
+        self
+    }
+
+    /// Limits the cache to `max_entries` verdicts, `0` disables caching.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets how long verdicts are cached.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Verifies that a request from `ip` with the parsed user agent `info`
+    /// comes from the bot it claims to be.
+    pub fn verify<P>(&self, info: &P, ip: IpAddr) -> Verification
+    where
+        P: UserAgentInformation + ?Sized,
+    {
+        self.verify_user_agent(info.user_agent(), ip)
+    }
+
+    /// Like `verify`, but identifies the bot from the user agent only.
+    pub fn verify_user_agent(&self, user_agent: &str, ip: IpAddr) -> Verification {
+        let bot = match self.bots.find(user_agent) {
+            Some(bot) if !bot.hostnames.is_empty() => bot,
+            _ => return Verification::Unverifiable,
+        };
+        let ip = canonical(ip);
+        let key = (ip, bot.name.clone());
+        if let Some(&(verdict, at)) = self.lock_cache().get(&key) {
+            if at.elapsed() < self.ttl {
+                return verdict;
+            }
+        }
+        let verdict = match self.lookup(bot, ip) {
+            Ok(verdict) => verdict,
+            Err(_) => return Verification::Unverifiable,
+        };
+        if self.max_entries > 0 {
+            let mut cache = self.lock_cache();
+            while cache.len() >= self.max_entries && !cache.contains(&key) {
+                cache.pop_lru();
+            }
+            cache.put(key, (verdict, Instant::now()));
+        }
+        verdict
+    }
+
+    fn lookup(&self, bot: &BotEntry, ip: IpAddr) -> io::Result<Verification> {
+        for host in self.resolver.reverse(ip)? {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            if !bot.hostnames.iter().any(|domain| in_domain(&host, domain)) {
+                continue;
+            }
+            let addresses = self.resolver.forward(&host)?;
+            if addresses
+                .into_iter()
+                .any(|address| canonical(address) == ip)
+            {
+                return Ok(Verification::Verified);
+            }
+        }
+        Ok(Verification::Spoofed)
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R> fmt::Debug for BotVerifier<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BotVerifier")
+            .field("max_entries", &self.max_entries)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+/// Returns `true` if `host` is `domain` or one of its subdomains.
+fn in_domain(host: &str, domain: &str) -> bool {
+    match host.strip_suffix(domain) {
+        Some("") => true,
+        Some(prefix) => prefix.ends_with('.'),
+        None => false,
+    }
+}
+
+/// Maps IPv4-mapped IPv6 addresses to IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+struct TestResolver {
+    ptr: std::collections::HashMap<IpAddr, Vec<String>>,
+    hosts: std::collections::HashMap<String, Vec<IpAddr>>,
+    lookups: std::cell::Cell<usize>,
+}
+
+#[cfg(test)]
+impl Resolver for TestResolver {
+    fn reverse(&self, ip: IpAddr) -> io::Result<Vec<String>> {
+        self.lookups.set(self.lookups.get() + 1);
+        if ip == IpAddr::from([192, 0, 2, 99]) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+        }
+        Ok(self.ptr.get(&ip).cloned().unwrap_or_default())
+    }
+
+    fn forward(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        Ok(self.hosts.get(host).cloned().unwrap_or_default())
+    }
+}
+
+#[test]
+fn test_verify() {
+    let google = IpAddr::from([66, 249, 66, 1]);
+    let spoofer = IpAddr::from([192, 0, 2, 1]);
+    let fake_ptr = IpAddr::from([192, 0, 2, 2]);
+    let resolver = TestResolver {
+        ptr: vec![
+            (google, vec!["crawl-66-249-66-1.GoogleBot.com.".to_string()]),
+            (
+                fake_ptr,
+                vec!["crawl.googlebot.com.evil.example".to_string()],
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        hosts: vec![
+            ("crawl-66-249-66-1.googlebot.com".to_string(), vec![google]),
+            (
+                "crawl.googlebot.com.evil.example".to_string(),
+                vec![fake_ptr],
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        lookups: std::cell::Cell::new(0),
+    };
+    let verifier = BotVerifier::new(&resolver);
+    let googlebot = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, google),
+        Verification::Verified
+    );
+    let mapped: IpAddr = "::ffff:66.249.66.1".parse().unwrap();
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, mapped),
+        Verification::Verified
+    );
+    assert_eq!(resolver.lookups.get(), 1);
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, spoofer),
+        Verification::Spoofed
+    );
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, fake_ptr),
+        Verification::Spoofed
+    );
+    let timeout = IpAddr::from([192, 0, 2, 99]);
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, timeout),
+        Verification::Unverifiable
+    );
+    assert_eq!(
+        verifier.verify_user_agent(googlebot, timeout),
+        Verification::Unverifiable
+    );
+    // Failed lookups are retried
+    assert_eq!(resolver.lookups.get(), 5);
+    // Bots without host names and other user agents
+    let gptbot = "Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; GPTBot/1.2; +https://openai.com/gptbot)";
+    assert_eq!(
+        verifier.verify_user_agent(gptbot, google),
+        Verification::Unverifiable
+    );
+    assert_eq!(
+        verifier.verify_user_agent("curl/7.64.1", google),
+        Verification::Unverifiable
+    );
+}
@@ -0,0 +1,243 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Maximum number of numeric components of a `UaVersion`.
+const MAX_COMPONENTS: usize = 4;
+
+/// `UaVersion` is the version of a browser, operating system or engine.
+///
+/// It consists of up to four numeric components separated by `.` or `_`,
+/// e.g. `10.0.19041` or `10_6_8`, and an optional label for the rest, e.g.
+/// `b3` in `3.0b3` or `XP`. The version is displayed exactly as it was
+/// parsed.
+///
+/// Versions are compared by their components, missing components count as
+/// zero, so `12.1 == 12.1.0`. A version with a label sorts before the same
+/// version without one, like a pre-release.
+///
+/// ```rust
+/// use uap_rust::UaVersion;
+/// let version: UaVersion = "12.1.2".parse().unwrap();
+/// assert!(version.at_least("12.1"));
+/// assert!(version.matches("12"));
+/// assert_eq!(version.minor(), 1);
+/// assert_eq!(version.to_string(), "12.1.2");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UaVersion {
+    components: [u64; MAX_COMPONENTS],
+    len: usize,
+    label: Option<String>,
+    text: String,
+}
+
+impl UaVersion {
+    /// Parses the version fields of a result, e.g. `major`, `minor` and
+    /// `patch` of a `Browser`. Fields after the first missing one are
+    /// ignored. Returns `None` if the first field is missing.
+    pub(crate) fn from_fields<'a, I>(fields: I) -> Option<UaVersion>
+    where
+        I: IntoIterator<Item = Option<&'a str>>,
+    {
+        let fields: Vec<&str> = fields.into_iter().map_while(|f| f).collect();
+        if fields.is_empty() {
+            return None;
+        }
+        Some(UaVersion::parse(fields.join(".")))
+    }
+
+    fn parse(text: String) -> UaVersion {
+        let mut components = [0; MAX_COMPONENTS];
+        let mut len = 0;
+        let mut rest = &text[..];
+        while len < MAX_COMPONENTS {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let component = match rest[..digits].parse() {
+                Ok(component) => component,
+                Err(_) => break,
+            };
+            components[len] = component;
+            len += 1;
+            rest = &rest[digits..];
+            // macOS versions are written like `10_6_8` in user agents
+            match rest.strip_prefix(['.', '_']) {
+                Some(next) if next.starts_with(|c: char| c.is_ascii_digit()) => rest = next,
+                _ => break,
+            }
+        }
+        let label = rest.trim_start_matches(['.', '-', '_', ' ']);
+        let label = Some(label.to_string()).filter(|l| !l.is_empty());
+        UaVersion {
+            components,
+            len,
+            label,
+            text,
+        }
+    }
+
+    /// Returns the numeric components.
+    pub fn components(&self) -> &[u64] {
+        &self.components[..self.len]
+    }
+
+    /// Returns the non-numeric rest of the version.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn major(&self) -> u64 {
+        self.components[0]
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.components[1]
+    }
+
+    pub fn patch(&self) -> u64 {
+        self.components[2]
+    }
+
+    pub fn patch_minor(&self) -> u64 {
+        self.components[3]
+    }
+
+    /// Returns `true` if the version is greater than or equal to `version`.
+    /// Returns `false` if `version` has no numeric component.
+    pub fn at_least(&self, version: &str) -> bool {
+        let version = UaVersion::parse(version.trim().into());
+        version.len > 0 && *self >= version
+    }
+
+    /// Returns `true` if the components given in `version` are equal, e.g.
+    /// `12.1.2` matches `12` and `12.1`, but not `12.2`.
+    pub fn matches(&self, version: &str) -> bool {
+        self.starts_with(&UaVersion::parse(version.trim().into()))
+    }
+
+    pub(crate) fn starts_with(&self, prefix: &UaVersion) -> bool {
+        prefix.len > 0 && prefix.components() == &self.components[..prefix.len]
+    }
+}
+
+impl FromStr for UaVersion {
+    type Err = ParseVersionError;
+
+    /// Parses a version. Fails only if the string is empty.
+    fn from_str(s: &str) -> Result<UaVersion, ParseVersionError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseVersionError(()));
+        }
+        Ok(UaVersion::parse(s.into()))
+    }
+}
+
+impl fmt::Display for UaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Ord for UaVersion {
+    fn cmp(&self, other: &UaVersion) -> Ordering {
+        self.components
+            .cmp(&other.components)
+            .then_with(|| match (&self.label, &other.label) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+impl PartialOrd for UaVersion {
+    fn partial_cmp(&self, other: &UaVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for UaVersion {
+    fn eq(&self, other: &UaVersion) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for UaVersion {}
+
+impl Hash for UaVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.components.hash(state);
+        self.label.hash(state);
+    }
+}
+
+/// Error returned when parsing an empty version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(());
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("empty version")
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+#[test]
+fn test_parse() {
+    let version = |s: &str| s.parse::<UaVersion>().unwrap();
+    let v = version("10.0.19041.1");
+    assert_eq!(v.components(), &[10, 0, 19041, 1]);
+    assert_eq!(v.label(), None);
+    let v = version("3.0b3pre");
+    assert_eq!(v.components(), &[3, 0]);
+    assert_eq!(v.label(), Some("b3pre"));
+    let v = version("XP");
+    assert_eq!(v.components(), &[] as &[u64]);
+    assert_eq!(v.label(), Some("XP"));
+    assert_eq!(version("2-dev").label(), Some("dev"));
+    assert_eq!(version("1.2.3.4.5").label(), Some("5"));
+    assert_eq!(version("1.05").to_string(), "1.05");
+    let v = version("10_6_8");
+    assert_eq!(v.components(), &[10, 6, 8]);
+    assert_eq!(v.label(), None);
+    assert_eq!(v.to_string(), "10_6_8");
+    assert_eq!(version("10_6").to_string(), "10_6");
+    assert!("".parse::<UaVersion>().is_err());
+
+    let fields = |f: &[Option<&str>]| UaVersion::from_fields(f.iter().cloned());
+    let v = fields(&[Some("10"), Some("6"), Some("8"), Some("update1")]).unwrap();
+    assert_eq!(v.components(), &[10, 6, 8]);
+    assert_eq!(v.label(), Some("update1"));
+    assert_eq!(v.to_string(), "10.6.8.update1");
+    assert_eq!(
+        fields(&[Some("7"), None, Some("1")]).unwrap().to_string(),
+        "7"
+    );
+    assert_eq!(fields(&[None, Some("1")]), None);
+}
+
+#[test]
+fn test_ordering() {
+    let version = |s: &str| s.parse::<UaVersion>().unwrap();
+    assert!(version("12.10") > version("12.9"));
+    assert!(version("12.1.0.1") > version("12.1"));
+    assert_eq!(version("12.1"), version("12.1.0"));
+    assert_eq!(version("1.05"), version("1.5"));
+    assert!(version("3.0b3") < version("3.0"));
+    assert!(version("3.0a1") < version("3.0b3"));
+    assert!(version("15.4").at_least("15.4"));
+    assert!(!version("15.3.9").at_least("15.4"));
+    assert!(!version("15.4").at_least("Vista"));
+    assert!(version("10_6") > version("10"));
+    assert_eq!(version("10_6"), version("10.6"));
+    assert!(version("10_6").at_least("10.5"));
+    assert!(!version("10_4_11").at_least("10_5"));
+    assert!(version("15.4.1").matches("15.4"));
+    assert!(!version("15.4.1").matches("15.5"));
+}
@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+
+use crate::engine::{is_ios, token_version};
+use crate::WebView;
+
+fn webview<'a>(app: Option<&'static str>, version: Option<&'a str>) -> WebView<'a> {
+    WebView {
+        app: app.map(Cow::Borrowed),
+        version: version.map(Cow::Borrowed),
+    }
+}
+
+/// Returns the in-app browser of Facebook or Messenger, which report the
+/// app in `FBAN/` on iOS and `FB_IAB/` on Android.
+fn facebook(agent: &str) -> Option<WebView<'_>> {
+    let start = agent.find("FBAN/").or_else(|| agent.find("FB_IAB/"))?;
+    let name = &agent[start..];
+    let name = &name[name.find('/')? + 1..];
+    let app = if name.starts_with("Messenger") || name.starts_with("Orca") {
+        "Messenger"
+    } else {
+        "Facebook"
+    };
+    Some(webview(Some(app), token_version(agent, "FBAV/")))
+}
+
+/// Returns the in-app browser of TikTok, named `musical_ly` or `trill`
+/// depending on the region.
+fn tiktok(agent: &str) -> Option<WebView<'_>> {
+    if !["musical_ly_", "trill_", "BytedanceWebview/"]
+        .iter()
+        .any(|token| agent.contains(token))
+    {
+        return None;
+    }
+    // `musical_ly_` is followed by either the version or a build number
+    let version = token_version(agent, "app_version/")
+        .or_else(|| token_version(agent, "musical_ly_").filter(|v| v.contains('.')));
+    Some(webview(Some("TikTok"), version))
+}
+
+pub(crate) fn parse_webview(agent: &str) -> Option<WebView<'_>> {
+    if let Some(version) = token_version(agent, "Instagram ") {
+        return Some(webview(Some("Instagram"), Some(version)));
+    }
+    if let Some(app) = facebook(agent).or_else(|| tiktok(agent)) {
+        return Some(app);
+    }
+    if let Some(version) = token_version(agent, " Line/") {
+        return Some(webview(Some("LINE"), Some(version)));
+    }
+    if let Some(version) = token_version(agent, "MicroMessenger/") {
+        return Some(webview(Some("WeChat"), Some(version)));
+    }
+    // Android WebView of any other app
+    if agent.contains("; wv)") {
+        return Some(webview(None, None));
+    }
+    // WKWebView lacks the `Safari/` token all browsers on iOS send
+    if is_ios(agent) && agent.contains("AppleWebKit/") && !agent.contains("Safari/") {
+        return Some(webview(None, None));
+    }
+    None
+}
+
+#[test]
+fn test_parse_webview() {
+    let messenger = parse_webview("Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/MessengerForiOS;FBAV/425.0.0.25.107;FBBV/514543563]").unwrap();
+    assert_eq!(messenger.app.as_deref(), Some("Messenger"));
+    assert_eq!(messenger.version.as_deref(), Some("425.0.0.25.107"));
+    let chrome = "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36";
+    assert_eq!(parse_webview(chrome), None);
+    assert_eq!(
+        parse_webview(
+            "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/119.0"
+        ),
+        None
+    );
+}
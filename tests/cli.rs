@@ -0,0 +1,64 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn uap(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_uap"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+static AGENTS: &str = "\
+Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/60.0
+Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)\r
+unknown, \"agent\"
+";
+
+#[test]
+fn test_json_lines() {
+    let output = uap(&["--fields", "browser_family,device_brand"], AGENTS);
+    assert_eq!(
+        output,
+        "{\"browser_family\":\"Firefox\",\"device_brand\":null}\n\
+         {\"browser_family\":\"Googlebot\",\"device_brand\":\"Spider\"}\n\
+         {\"browser_family\":\"Other\",\"device_brand\":null}\n"
+    );
+}
+
+#[test]
+fn test_csv_and_tsv() {
+    let output = uap(&["-f", "csv", "--fields=user_agent,os_family"], AGENTS);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines[0], "user_agent,os_family");
+    assert!(lines[1].ends_with("Firefox/60.0,Linux"));
+    assert_eq!(lines[3], "\"unknown, \"\"agent\"\"\",Other");
+
+    let output = uap(
+        &["-f", "tsv", "--no-header", "--fields", "browser_major"],
+        AGENTS,
+    );
+    assert_eq!(output, "60\n2\n\n");
+}
+
+#[test]
+fn test_only_unmatched() {
+    let output = uap(
+        &["--only-unmatched", "--fields", "user_agent,browser_family"],
+        AGENTS,
+    );
+    assert_eq!(
+        output,
+        "{\"user_agent\":\"unknown, \\\"agent\\\"\",\"browser_family\":\"Other\"}\n"
+    );
+}
@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
+
 use lazy_static::lazy_static;
 use rmp_serde as rmps;
 use serde_derive::Deserialize;
-use uap_rust::{Browser, Device, OS};
+use uap_rust::{Browser, Device, Engine, WebView, OS};
 
 lazy_static! {
     static ref BROWSER_TEST: Vec<&'static [u8]> = {
@@ -20,7 +22,10 @@ lazy_static! {
         ]
     };
     static ref DEVICE_TEST: Vec<&'static [u8]> =
-        { vec![include_bytes!("../../resources/tests/test_device.msgpack")] };
+        vec![include_bytes!("../../resources/tests/test_device.msgpack")];
+    static ref ENGINE_TEST: Vec<&'static str> = vec![include_str!("test_engine.yaml")];
+    static ref AUTOMATION_TEST: Vec<&'static str> = vec![include_str!("test_automation.yaml")];
+    static ref WEBVIEW_TEST: Vec<&'static str> = vec![include_str!("test_webview.yaml")];
 }
 
 #[derive(Debug, Deserialize)]
@@ -145,3 +150,97 @@ pub fn parse_device_test_cases() -> Vec<(&'static str, Device<'static>)> {
         })
         .collect()
 }
+
+#[derive(Debug, Deserialize)]
+struct EngineTestCase {
+    user_agent_string: String,
+    family: String,
+    major: Option<String>,
+    minor: Option<String>,
+    patch: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EngineTestCases {
+    test_cases: Vec<EngineTestCase>,
+}
+
+pub fn parse_engine_test_cases() -> Vec<(String, Engine<'static>)> {
+    let mut all_cases = Vec::new();
+    for cases in ENGINE_TEST.iter() {
+        let mut cases = serde_yaml::from_str::<EngineTestCases>(cases).unwrap();
+        all_cases.append(&mut cases.test_cases);
+    }
+    all_cases
+        .into_iter()
+        .map(|case| {
+            (
+                case.user_agent_string,
+                Engine {
+                    family: case.family.into(),
+                    major: case.major.map(<_>::into),
+                    minor: case.minor.map(<_>::into),
+                    patch: case.patch.map(<_>::into),
+                },
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutomationTestCase {
+    pub user_agent_string: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub tool: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AutomationTestCases {
+    test_cases: Vec<AutomationTestCase>,
+}
+
+pub fn parse_automation_test_cases() -> Vec<AutomationTestCase> {
+    let mut all_cases = Vec::new();
+    for cases in AUTOMATION_TEST.iter() {
+        let mut cases = serde_yaml::from_str::<AutomationTestCases>(cases).unwrap();
+        all_cases.append(&mut cases.test_cases);
+    }
+    all_cases
+}
+
+#[derive(Debug, Deserialize)]
+struct WebViewTestCase {
+    user_agent_string: String,
+    webview: bool,
+    app: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebViewTestCases {
+    test_cases: Vec<WebViewTestCase>,
+}
+
+pub fn parse_webview_test_cases() -> Vec<(String, Option<WebView<'static>>)> {
+    let mut all_cases = Vec::new();
+    for cases in WEBVIEW_TEST.iter() {
+        let mut cases = serde_yaml::from_str::<WebViewTestCases>(cases).unwrap();
+        all_cases.append(&mut cases.test_cases);
+    }
+    all_cases
+        .into_iter()
+        .map(|case| {
+            let webview = if case.webview {
+                Some(WebView {
+                    app: case.app.map(<_>::into),
+                    version: case.version.map(<_>::into),
+                })
+            } else {
+                None
+            };
+            (case.user_agent_string, webview)
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
+use uap_rust::client_hints::ClientHints;
 use uap_rust::unsync::BorrowingParser as Parser;
-use uap_rust::{Browser, Device, OS};
+use uap_rust::{Browser, Device, UserAgentInformation, OS};
 
 mod test_data;
 
@@ -66,10 +67,73 @@ fn test_os() {
     }
 }
 
+#[test]
+fn test_engine() {
+    let cases = test_data::parse_engine_test_cases();
+    for (uas, test_engine) in cases.iter() {
+        let parser = Parser::new(uas);
+        let engine = parser.engine();
+        assert_eq!(engine, test_engine, "{}", uas);
+    }
+}
+
+#[test]
+fn test_webview() {
+    let cases = test_data::parse_webview_test_cases();
+    for (uas, test_webview) in cases.iter() {
+        let parser = Parser::new(uas);
+        assert_eq!(parser.webview(), test_webview.as_ref(), "{}", uas);
+        assert_eq!(parser.is_webview(), test_webview.is_some(), "{}", uas);
+    }
+}
+
+#[test]
+fn test_automation() {
+    for case in test_data::parse_automation_test_cases() {
+        let parser = Parser::new(&case.user_agent_string);
+        let hints = ClientHints::from_headers(
+            case.headers
+                .iter()
+                .map(|(name, value)| (&name[..], &value[..])),
+        );
+        let automation = hints.automation(&parser);
+        let tool = automation.as_ref().map(|a| a.tool.as_str());
+        let version = automation.as_ref().and_then(|a| a.version.as_deref());
+        assert_eq!(tool, case.tool.as_deref(), "{}", case.user_agent_string);
+        assert_eq!(
+            version,
+            case.version.as_deref(),
+            "{}",
+            case.user_agent_string
+        );
+        if case.headers.is_empty() {
+            assert_eq!(parser.automation(), automation);
+        }
+    }
+}
+
 #[test]
 fn test_is_bot() {
     let cases = test_data::parse_device_test_cases();
     for &(uas, ref test_device) in cases.iter() {
-        assert_eq!(Parser::new(uas).is_bot(), &*test_device.family == "Spider");
+        let parser = Parser::new(uas);
+        assert_eq!(parser.is_bot(), &*test_device.family == "Spider", "{}", uas);
+        // The device is shared with the bot check
+        assert_eq!(parser.device(), test_device, "{}", uas);
+    }
+    let cases = test_data::parse_browser_test_cases();
+    for &(uas, _) in cases.iter() {
+        let is_spider = Parser::new(uas).device().family == "Spider";
+        assert_eq!(Parser::new(uas).is_bot(), is_spider, "{}", uas);
+    }
+}
+
+#[test]
+fn test_explain() {
+    let cases = test_data::parse_browser_test_cases();
+    for &(uas, ref test_browser) in cases.iter() {
+        let explanation = Parser::new(uas).explain();
+        let browser = explanation.browser.map(|b| b.value).unwrap_or_default();
+        assert_eq!(&browser, test_browser, "{}", uas);
     }
 }